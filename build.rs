@@ -31,8 +31,8 @@ fn main() {
         .to_str()
         .unwrap_or_else(|| error("Could not convert path to string"))
         .to_string();
-    let script_rls = format!("#!/bin/sh\n{pwd}/target/release/unicore $@\n");
-    let script_dbg = format!("#!/bin/sh\n{pwd}/target/debug/unicore $@\n");
+    let script_rls = format!("#!/bin/sh\n{pwd}/target/release/unicore \"$@\"\n");
+    let script_dbg = format!("#!/bin/sh\n{pwd}/target/debug/unicore \"$@\"\n");
 
     let mut file = File::create("bin/unicore").unwrap();
     file.write_all(script_rls.as_bytes()).unwrap();
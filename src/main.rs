@@ -38,6 +38,21 @@ fn run(args: &parser::Args, bin: &var::BinaryPaths, test: bool) -> Result<(), Bo
         Some(parser::Commands::GeneTree { .. }) => {
             modules::genetree::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
         }
+        Some(parser::Commands::RemoveSpecies { .. }) => {
+            modules::removespecies::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
+        },
+        Some(parser::Commands::LookupIndex { .. }) => {
+            modules::lookupindex::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
+        },
+        Some(parser::Commands::LookupBuild { .. }) => {
+            modules::lookupbuild::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
+        },
+        Some(parser::Commands::Cache { .. }) => {
+            modules::cache::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
+        },
+        Some(parser::Commands::ExportCore { .. }) => {
+            modules::exportcore::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
+        },
         Some(parser::Commands::EasyCore { .. }) => {
             workflow::easy_core::run(args, bin).unwrap_or_else(|e| err::error(err::ERR_GENERAL, Some(e.to_string())));
         },
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use crate::envs::error_handler as err;
+use crate::envs::variables as var;
+use crate::seq::prediction_cache::{self, PredictionCache};
+use crate::util::arg_parser::Args;
+use crate::util::message as msg;
+
+// Size in bytes in a human readable unit
+fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, units[unit]) }
+}
+
+pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
+    // Retrieve arguments
+    let dir = args.cache_dir.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cache - dir".to_string())); });
+    let max_size = args.cache_max_size.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cache - max_size".to_string())); });
+    let max_age = args.cache_max_age.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cache - max_age".to_string())); });
+
+    let dir = prediction_cache::cache_dir(dir).unwrap_or_else(|| {
+        err::error(err::ERR_ARGPARSE, Some(format!("No cache directory given; give one as an argument or set {}", prediction_cache::CACHE_ENV)));
+    });
+    if !Path::new(&dir).is_dir() {
+        err::error(err::ERR_GENERAL, Some(format!("Cache directory {} does not exist", dir)));
+    }
+    let cache = PredictionCache::open(&dir)?;
+
+    if max_size.is_some() || max_age.is_some() {
+        let (removed, removed_size) = cache.prune(max_size, max_age.map(|days| days * 24 * 3600))?;
+        msg::println_message(&format!("Removed {} entries ({}) from {}", removed, human_size(removed_size), dir), 3);
+    }
+    let (entries, size) = cache.size()?;
+    msg::println_message(&format!("{} entries ({}) in {}", entries, human_size(size), dir), 3);
+
+    Ok(())
+}
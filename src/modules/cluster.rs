@@ -1,11 +1,55 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use crate::envs::error_handler as err;
+use crate::seq::cluster_diff;
+use crate::seq::foldseek_db::DbReader;
 use crate::util::arg_parser::Args;
 use crate::util::command as cmd;
 use crate::util::checkpoint as chkpnt;
 use crate::util::message as msg;
 
-// Run foldseek cluster and createtsv
+// Keys of the members in a cluster database
+fn cluster_member_keys(cluster_db: &str) -> Result<HashSet<u32>, Box<dyn std::error::Error>> {
+    let reader = DbReader::open(cluster_db)?;
+    let mut keys = HashSet::new();
+    for key in reader.keys() {
+        for line in reader.get_str(key).unwrap_or_default().lines() {
+            if let Some(member) = line.split('\t').next().filter(|m| !m.is_empty()) {
+                keys.insert(member.parse()?);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+// Write the database of the sequences clustered in the previous run, as the subset of the input with the same keys
+// Returns false if some of the sequences are no longer in the input
+fn previous_db(path: &str, input: &str, cluster_db: &str, runner: &dyn Fn(&str, &[&str])) -> Result<bool, Box<dyn std::error::Error>> {
+    let members = cluster_member_keys(cluster_db)?;
+    let input_keys: HashSet<u32> = DbReader::open(input)?.keys().collect();
+    if !members.is_subset(&input_keys) {
+        return Ok(false);
+    }
+    let key_file = format!("{}.keys", path);
+    let mut writer = BufWriter::new(File::create(&key_file)?);
+    for key in &members {
+        writeln!(writer, "{}", key)?;
+    }
+    writer.flush()?;
+    for suffix in ["", "_ss", "_h"] {
+        let (original, subset) = (format!("{}{}", input, suffix), format!("{}{}", path, suffix));
+        if Path::new(&format!("{}.index", original)).exists() {
+            runner("createsubdb", &[&key_file, &original, &subset, "--subdb-mode", "0"]);
+        }
+    }
+    std::fs::remove_file(&key_file)?;
+    Ok(true)
+}
+
+// Run foldseek or mmseqs cluster and createtsv
+// In update mode, the previous clustering is extended by clusterupdate, keeping its clusters where possible
 pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
     // Retrieve arguments
     let input: String = args.cluster_input.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - input".to_string())); });
@@ -13,6 +57,8 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     let tmp: String = args.cluster_tmp.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - tmp".to_string())); });
     let keep_cluster_db: bool = args.cluster_keep_cluster_db.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - keep_cluster_db".to_string())); });
     let cluster_options: String = args.cluster_cluster_options.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - cluster_args".to_string())); });
+    let backend: String = args.cluster_backend.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - backend".to_string())); });
+    let update: bool = args.cluster_update.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - update".to_string())); });
     let threads = crate::envs::variables::threads();
     let threads_str = threads.to_string();
     let foldseek_verbosity = (match crate::envs::variables::verbosity() { 4 => 3, 3 => 2, _ => crate::envs::variables::verbosity() }).to_string();
@@ -34,51 +80,118 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     // cluster_arg into vector, parsing by space
     let cluster_args: Vec<&str> = cluster_options.split_whitespace().collect();
 
-    // Get foldseek
-    let foldseek_path = match &bin.get("foldseek") {
+    // Get foldseek, or mmseqs to cluster by the amino acid sequences of the same database
+    let foldseek_path = match &bin.get(&backend) {
         Some(bin) => &bin.path,
-        _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
+        _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some(backend.clone())); }
     };
 
     let output_cluster_db = format!("{}_cluster", output);
     let output_tsv = format!("{}.tsv", output);
-    let mut foldseek_flag = vec![
-        "cluster", "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(), &input, &output_cluster_db, &tmp,
-    ];
-    // Include cluster_args into foldseek_flag
-    foldseek_flag.extend(cluster_args.iter());
-
-    // Run foldseek cluster
-    msg::print_message(&"Running foldseek cluster...".to_string(), 3);
-    let mut cmd = std::process::Command::new(foldseek_path);
-    let mut cmd = cmd.args(&foldseek_flag);
-    cmd::run(&mut cmd);
-    msg::println_message(&" Done".to_string(), 3);
-
-    // Run foldseek createtsv
-    let mut cmd = std::process::Command::new(foldseek_path);
-    let foldseek_flag = vec![
-        "createtsv", "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(), &input, &input, &output_cluster_db, &output_tsv,
-    ];
-    let mut cmd = cmd.args(&foldseek_flag);
-    cmd::run(&mut cmd);
-
-    // Delete intermediate database
-    if !keep_cluster_db {
+    // Sequences of the last update, which the cluster database refers to
+    let clustered_db = format!("{}_cluster_seqs", output);
+    let fingerprint_file = format!("{}.fingerprint", output);
+    let run_db_command = |module: &str, command: &[&str]| {
+        cmd::run(std::process::Command::new(foldseek_path).arg(cmd::base_module(&backend, module)).args(command).arg("-v").arg(foldseek_verbosity.as_str()));
+    };
+    let db_exists = |db: &str| Path::new(&format!("{}.index", db)).exists();
+
+    // Update the previous clustering if there is one
+    let mut incremental = update;
+    if incremental && !(db_exists(&output_cluster_db) && Path::new(&output_tsv).exists()) {
+        err::warning(err::WRN_GENERAL, Some(format!("No previous cluster database {} to update; run cluster with --keep-cluster-db or --update first. Clustering from scratch", output_cluster_db)));
+        incremental = false;
+    }
+    if incremental && !db_exists(&clustered_db) && !previous_db(&clustered_db, &input, &output_cluster_db, &run_db_command)? {
+        err::warning(err::WRN_GENERAL, Some("Sequences of the previous clustering were removed from the database; clustering from scratch".to_string()));
+        incremental = false;
+    }
+
+    if incremental {
+        // Run foldseek clusterupdate, assigning the new sequences to the previous clusters
+        let updated_db = format!("{}_cluster_seqs_new", output);
+        let updated_cluster_db = format!("{}_cluster_new", output);
+        let updated_tsv = format!("{}.tsv.new", output);
+        let clusterupdate = cmd::base_module(&backend, "clusterupdate");
+        let mut foldseek_flag = vec![
+            clusterupdate.as_str(), "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(),
+            &clustered_db, &input, &output_cluster_db, &updated_db, &updated_cluster_db, &tmp,
+        ];
+        foldseek_flag.extend(cluster_args.iter());
+        msg::print_message(&format!("Running {} clusterupdate...", backend), 3);
+        cmd::run(std::process::Command::new(foldseek_path).args(&foldseek_flag));
+        msg::println_message(&" Done".to_string(), 3);
+
+        // Keys of the updated clustering refer to the updated sequence database
+        cmd::run(std::process::Command::new(foldseek_path).args([
+            "createtsv", "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(), &updated_db, &updated_db, &updated_cluster_db, &updated_tsv,
+        ]));
+
+        // Report the changes of the clusters
+        let changes = cluster_diff::compare(&cluster_diff::read_clusters(&output_tsv)?, &cluster_diff::read_clusters(&updated_tsv)?);
+        let report = format!("{}.update.tsv", output);
+        cluster_diff::write_changes(&report, &changes)?;
+        let count = |change: &str| changes.iter().filter(|c| c.changes.contains(&change)).count();
+        msg::println_message(&format!(
+            "Clusters updated: {} new, {} gained members, {} lost members, {} merged, {} split, {} removed; see {}",
+            count("new"), count("gained"), count("lost"), count("merged"), count("split"), count("removed"), report,
+        ), 3);
+
+        // Replace the previous clustering
+        std::fs::rename(&updated_tsv, &output_tsv)?;
+        for (updated, previous) in [(&updated_cluster_db, &output_cluster_db), (&updated_db, &clustered_db)] {
+            for suffix in ["", "_ss", "_h", "_ca"] {
+                let (updated, previous) = (format!("{}{}", updated, suffix), format!("{}{}", previous, suffix));
+                if db_exists(&previous) { run_db_command("rmdb", &[&previous]); }
+                if db_exists(&updated) { run_db_command("mvdb", &[&updated, &previous]); }
+            }
+        }
+    } else {
+        let mut foldseek_flag = vec![
+            "cluster", "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(), &input, &output_cluster_db, &tmp,
+        ];
+        // Include cluster_args into foldseek_flag
+        foldseek_flag.extend(cluster_args.iter());
+
+        // Run foldseek cluster
+        msg::print_message(&format!("Running {} cluster...", backend), 3);
+        let mut cmd = std::process::Command::new(foldseek_path);
+        let cmd = cmd.args(&foldseek_flag);
+        cmd::run(cmd);
+        msg::println_message(&" Done".to_string(), 3);
+
+        // Run foldseek createtsv
+        let mut cmd = std::process::Command::new(foldseek_path);
+        let foldseek_flag = vec![
+            "createtsv", "--threads", threads_str.as_str(), "-v", foldseek_verbosity.as_str(), &input, &input, &output_cluster_db, &output_tsv,
+        ];
+        let cmd = cmd.args(&foldseek_flag);
+        cmd::run(cmd);
+
+        // The new cluster database refers to the input, so a later update takes its subset again
+        for suffix in ["", "_ss", "_h", "_ca"] {
+            let db = format!("{}{}", clustered_db, suffix);
+            if db_exists(&db) { run_db_command("rmdb", &[&db]); }
+        }
+    }
+
+    // Delete intermediate database; kept in update mode for the next update
+    if !keep_cluster_db && !update {
         let mut cmd = std::process::Command::new(foldseek_path);
         let foldseek_flag = vec![
             "rmdb", 
             &output_cluster_db,
             "-v", foldseek_verbosity.as_str(),
         ];
-        let mut cmd = cmd.args(&foldseek_flag);
-        cmd::run(&mut cmd);
+        let cmd = cmd.args(&foldseek_flag);
+        cmd::run(cmd);
     }
 
     // TODO: Implement detection and removal of foldseek cluster temporary results
 
-    // Write the checkpoint file
+    // Write the fingerprint of the clustered database and the checkpoint file
+    chkpnt::write_fingerprint(&fingerprint_file, &input)?;
     chkpnt::write_checkpoint(&format!("{}/cluster.chk", parent), "1")?;
 
     Ok(())
-}
\ No newline at end of file
+}
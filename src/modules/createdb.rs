@@ -1,4 +1,10 @@
 use crate::seq::fasta_io as fasta;
+use crate::seq::proteome;
+use crate::seq::bucket;
+use crate::seq::validate;
+use crate::seq::afdb_lookup;
+use crate::seq::foldseek_db::{self, header_name, DbReader, SeqDbWriter};
+use crate::seq::prediction_cache::{self, PredictionCache};
 use crate::envs::variables as var;
 use crate::envs::error_handler as err;
 use crate::util::arg_parser::Args;
@@ -6,8 +12,8 @@ use crate::util::command as cmd;
 use crate::util::message as msg;
 use crate::util::checkpoint as chkpnt;
 
-use std::io::{BufWriter, Write};
-use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, MAIN_SEPARATOR as SEP};
 use std::process::Command as Cmd;
 
@@ -17,6 +23,274 @@ fn need_replacement(c: char) -> bool {
     c.is_whitespace() || c == ';' || c == ':' || c == ',' || c == '=' || c == '/' || c == '(' || c == ')'
 }
 
+// Set of input sequences of a species
+enum InputSet {
+    Proteome(proteome::Proteome),
+    // Directory of structure files (PDB, mmCIF, ...) of a species
+    Structures { species: String, dir: String },
+}
+
+// Collect the structure directories, one subdirectory per species
+fn collect_structures(dir: &str) -> Result<Vec<InputSet>, Box<dyn std::error::Error>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() { dirs.push(path); }
+    }
+    dirs.sort();
+    let mut sets = Vec::new();
+    for path in dirs {
+        let species = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if !proteome::is_newick_safe(&species) {
+            return Err(format!("Structure directory name '{}' contains whitespace or characters reserved in Newick format", species).into());
+        }
+        sets.push(InputSet::Structures { species, dir: path.to_string_lossy().into_owned() });
+    }
+    Ok(sets)
+}
+
+// When appending or removing species, the updated files of the database are staged under {output}.staged and swapped in
+// at the end, so that the database stays intact until the swap, and an interrupted swap is finished by the next run
+pub const STAGED: &str = ".staged";
+
+// Files of the database with their staged versions, as (file, staged)
+pub fn staged_files(output: &str) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    for suffix in ["", "_ss", "_h"] {
+        for ext in ["", ".index", ".dbtype", ".lookup"] {
+            files.push((format!("{}{}{}", output, suffix, ext), format!("{}{}{}{}", output, STAGED, suffix, ext)));
+        }
+    }
+    for ext in [".map", ".provenance.tsv", ".meta"] {
+        files.push((format!("{}{}", output, ext), format!("{}{}{}", output, STAGED, ext)));
+    }
+    files
+}
+
+// Stage a copy of a table of the database with the rows of the new species appended
+// A table the database doesn't have yet, e.g. the provenance of an older database, gets the new rows only
+fn stage_rows(new_file: &str, file: &str, staged: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if Path::new(file).exists() {
+        std::fs::copy(file, staged)?;
+        let mut writer = std::fs::OpenOptions::new().append(true).open(staged)?;
+        std::io::copy(&mut std::fs::File::open(new_file)?, &mut writer)?;
+    } else {
+        std::fs::copy(new_file, staged)?;
+    }
+    std::fs::remove_file(new_file)?;
+    Ok(())
+}
+
+// Remove the staged files left over by a run interrupted before the swap; the database itself is intact
+pub fn remove_staged(output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    for (_, staged) in staged_files(output) {
+        if Path::new(&staged).exists() { std::fs::remove_file(&staged)?; }
+    }
+    Ok(())
+}
+
+// Replace the files of the database by their staged versions, and remove the marker of the pending swap
+fn swap_staged(output: &str, marker: &str) -> Result<(), Box<dyn std::error::Error>> {
+    for (file, staged) in staged_files(output) {
+        if Path::new(&staged).exists() {
+            std::fs::rename(&staged, &file)?;
+        }
+    }
+    std::fs::remove_file(marker)?;
+    Ok(())
+}
+
+// Swap all staged files into the database
+// The database is marked unfinished while the marker of the swap exists; the marker holds the command that staged the files
+pub fn commit_staged(output: &str, command: &str, marker: &str, checkpoint_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    chkpnt::write_checkpoint(checkpoint_file, "0")?;
+    chkpnt::write_checkpoint(marker, command)?;
+    swap_staged(output, marker)?;
+    chkpnt::write_checkpoint(checkpoint_file, "1")?;
+    Ok(())
+}
+
+// Finish the swap of a run interrupted while swapping its staged files into the database
+// Returns the command of the interrupted run, if there was one
+pub fn finish_staged(output: &str, marker: &str, checkpoint_file: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if !Path::new(marker).exists() { return Ok(None); }
+    let command = chkpnt::read_checkpoint(marker)?;
+    msg::println_message(&format!("Finishing the interrupted {} run on {}", command, output), 3);
+    swap_staged(output, marker)?;
+    chkpnt::write_checkpoint(checkpoint_file, "1")?;
+    Ok(Some(command))
+}
+
+// Stage the tables of the new species and swap all staged files into the database
+fn commit_append(output: &str, new_tables: &[(String, String)], marker: &str, checkpoint_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let staged: HashMap<String, String> = staged_files(output).into_iter().collect();
+    for (new_file, file) in new_tables {
+        if Path::new(new_file).exists() {
+            stage_rows(new_file, file, &staged[file])?;
+        }
+    }
+    commit_staged(output, "append", marker, checkpoint_file)
+}
+
+// Take the 3Di sequences of the sequences left for prediction from the cache, and write the provenance of the rest
+// Returns the number of sequences to predict
+fn apply_cache<W: Write>(combined_aa: &str, cache: Option<&PredictionCache>, converted: &mut SeqDbWriter, provenance: &mut W) -> Result<usize, Box<dyn std::error::Error>> {
+    let remaining_aa = format!("{}.remaining", combined_aa);
+    let mut writer = BufWriter::new(std::fs::File::create(&remaining_aa)?);
+    let (mut predicted, mut cached) = (0, 0);
+    for record in fasta::fasta_records(combined_aa)? {
+        let (name, seq) = record?;
+        match cache.and_then(|c| c.get(&seq)) {
+            Some(ss) => {
+                converted.add(&name, &seq, &ss)?;
+                writeln!(provenance, "{}\tcache\t-\t-", name)?;
+                cached += 1;
+            },
+            None => {
+                writeln!(writer, ">{}\n{}", name, seq)?;
+                writeln!(provenance, "{}\tpredicted\t-\t-", name)?;
+                predicted += 1;
+            },
+        }
+    }
+    writer.flush()?;
+    std::fs::rename(&remaining_aa, combined_aa)?;
+    if cache.is_some() {
+        msg::println_message(&format!("{} sequences found in the prediction cache; {} sequences left to predict", cached, predicted), 3);
+    }
+    Ok(predicted)
+}
+
+// Store the predicted 3Di sequences of a shard in the cache
+fn cache_shard(cache: &PredictionCache, shard_db: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let aa = DbReader::open(shard_db)?;
+    let ss = DbReader::open(&format!("{}_ss", shard_db))?;
+    let mut count = 0;
+    for key in aa.keys() {
+        if let (Some(aa_seq), Some(ss_seq)) = (aa.get_str(key), ss.get_str(key)) {
+            cache.put(&aa_seq, &ss_seq)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// Entry named in a bucket: (name, amino acid sequence, 3Di sequence and its source, new entry)
+type BucketEntry = (String, String, Option<(String, &'static str)>, bool);
+
+// Files of a prediction shard: fasta file, database and checkpoint
+fn shard_files(shard_dir: &str, index: usize) -> (String, String, String) {
+    let prefix = format!("{}{}shard_{:05}", shard_dir, SEP, index);
+    (format!("{}.fasta", prefix), format!("{}_db", prefix), format!("{}.chk", prefix))
+}
+
+// Split the combined fasta file into shards of shard_size sequences and return the number of shards
+// A shard whose fasta file is unchanged keeps its checkpoint, so that its prediction is not repeated
+fn split_shards(combined_aa: &str, shard_dir: &str, shard_size: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(shard_dir)?;
+    let mut shards = 0;
+    let mut records = fasta::fasta_records(combined_aa)?.peekable();
+    while records.peek().is_some() {
+        let mut content = String::new();
+        for record in records.by_ref().take(shard_size) {
+            let (header, seq) = record?;
+            content.push_str(&format!(">{}\n{}\n", header, seq));
+        }
+        let (shard_fasta, _, shard_chk) = shard_files(shard_dir, shards);
+        if std::fs::read_to_string(&shard_fasta).ok().as_deref() != Some(content.as_str()) {
+            std::fs::write(&shard_fasta, &content)?;
+            chkpnt::write_checkpoint(&shard_chk, "0")?;
+        }
+        shards += 1;
+    }
+    Ok(shards)
+}
+
+// Stream of the input records into the mapping file and the buckets
+struct RecordStream {
+    buckets: bucket::Buckets,
+    mapping_writer: BufWriter<std::fs::File>,
+    // Number of rows written to the mapping file
+    rows: usize,
+    existing_species: HashSet<String>,
+    rules: validate::Rules,
+    // Report of the dropped and changed sequences
+    report: BufWriter<std::fs::File>,
+    report_file: String,
+    // Numbers of read, dropped, changed and nucleotide sequences of the current file
+    read: usize,
+    dropped: usize,
+    changed: usize,
+    nucleotide: usize,
+    // Number of kept short sequences made only of nucleotide letters
+    nucleotide_letters: usize,
+}
+
+impl RecordStream {
+    fn add(&mut self, file: &str, species: String, key: String, value: String, ss: Option<String>, origin: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.existing_species.contains(&species) {
+            err::error(err::ERR_GENERAL, Some(format!("Species {} already exists in the database", species)));
+        }
+        self.read += 1;
+        let original_len = value.len();
+        let (value, changes) = match validate::validate(&value, &self.rules) {
+            Ok(valid) => valid,
+            Err(reason) => {
+                writeln!(self.report, "{}\t{}\t{}\tdropped\t{}", file, species, key, reason)?;
+                self.dropped += 1;
+                if reason == validate::NUCLEOTIDE { self.nucleotide += 1; }
+                return Ok(());
+            },
+        };
+        if !changes.is_empty() {
+            writeln!(self.report, "{}\t{}\t{}\tchanged\t{}", file, species, key, changes.join("; "))?;
+            self.changed += 1;
+        }
+        if validate::nucleotide_letters(&value) {
+            writeln!(self.report, "{}\t{}\t{}\tkept\tonly nucleotide letters", file, species, key)?;
+            self.nucleotide_letters += 1;
+        }
+        // 3Di sequences covering the removed terminal stop codons are trimmed as well
+        let ss = ss.map(|mut ss| {
+            if ss.len() == original_len { ss.truncate(value.len()); }
+            ss
+        });
+        if let Some(ss) = &ss {
+            if ss.len() != value.len() {
+                err::error(err::ERR_GENERAL, Some(format!("3Di sequence of {} in {} has length {}, but the amino acid sequence has length {}", key, species, ss.len(), value.len())));
+            }
+        }
+        // replace all whitespace characters with underscore
+        let key = key.replace(|c: char| need_replacement(c), "_");
+
+        // Name the sequence by its full MD5 hash; hash collisions are resolved when the buckets are read
+        let hash = format!("{:x}", md5::compute(value.as_bytes()));
+        let name = format!("unicore_{}", hash);
+        // Every occurrence is written to the mapping file, so shared sequences are listed once per gene
+        writeln!(self.mapping_writer, "{}\t{}\t{}\t{}", name, species, key, origin)?;
+        let row = self.rows.to_string();
+        self.rows += 1;
+        self.buckets.write(bucket::index(&hash), &[&name, "-", &value, ss.as_deref().unwrap_or(""), origin, &row, &species, &key])?;
+        Ok(())
+    }
+
+    // Summarize the validation of a file and reset the counts
+    fn finish_file(&mut self, file: &str) {
+        if self.dropped > 0 || self.changed > 0 {
+            msg::println_message(&format!("{}: {} of {} sequences dropped and {} changed; see {}", file, self.dropped, self.read, self.changed, self.report_file), 3);
+        }
+        if self.nucleotide * 2 > self.read {
+            err::warning(err::WRN_GENERAL, Some(format!("{} looks like a nucleotide file ({} of {} sequences); give amino acid sequences, or GFF3, GenBank or EMBL files to translate", file, self.nucleotide, self.read)));
+        } else if self.nucleotide > 0 {
+            err::warning(err::WRN_GENERAL, Some(format!("{}: {} sequences look like nucleotide sequences and are dropped; see {}", file, self.nucleotide, self.report_file)));
+        }
+        if self.nucleotide_letters > 0 {
+            err::warning(err::WRN_GENERAL, Some(format!("{}: {} sequences shorter than {} residues have only nucleotide letters and are kept as amino acid sequences; see {}", file, self.nucleotide_letters, validate::NUCLEOTIDE_MIN_LEN, self.report_file)));
+        }
+        (self.read, self.dropped, self.changed, self.nucleotide, self.nucleotide_letters) = (0, 0, 0, 0, 0);
+    }
+}
+
 pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
     // Retrieve mandatory arguments
     let input = args.createdb_input.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - input".to_string())); });
@@ -25,12 +299,27 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
     let keep = args.createdb_keep.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - keep".to_string())); });
     let overwrite = args.createdb_overwrite.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - overwrite".to_string())); });
     let max_len = args.createdb_max_len.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - max_len".to_string())); });
+    let min_len = args.createdb_min_len.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - min_len".to_string())); });
+    let max_ambiguous = args.createdb_max_ambiguous.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - max_ambiguous".to_string())); });
     let gpu = args.createdb_gpu.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - gpu".to_string())); });
     let afdb_lookup = args.createdb_afdb_lookup.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - afdb_lookup".to_string())); });
+    let afdb_url = args.createdb_afdb_url.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - afdb_url".to_string())); });
+    let min_plddt = args.createdb_min_plddt.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - min_plddt".to_string())); });
+    let cache = args.createdb_cache.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - cache".to_string())); });
+    let lookup_download = args.createdb_lookup_download.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - lookup_download".to_string())); });
     let custom_lookup = args.createdb_custom_lookup.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - custom_lookup".to_string())); });
+    let genetic_code = args.createdb_genetic_code.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - genetic_code".to_string())); });
+    let header_species = args.createdb_header_species.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - header_species".to_string())); });
+    let precomputed_3di = args.createdb_precomputed_3di.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - precomputed_3di".to_string())); });
+    let structures = args.createdb_structures.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - structures".to_string())); });
+    let append = args.createdb_append.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - append".to_string())); });
+    let shard_size = args.createdb_shard_size.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("createdb - shard_size".to_string())); });
     let threads = crate::envs::variables::threads();
     let foldseek_verbosity = (match var::verbosity() { 4 => 3, 3 => 2, _ => var::verbosity() }).to_string();
 
+    // Parse the rule of taking species labels from fasta headers
+    let header_species = header_species.map(|pattern| proteome::HeaderSpecies::new(&pattern).unwrap_or_else(|e| { err::error(err::ERR_ARGPARSE, Some(e)); }));
+
     // Check if lookup options conflict
     if afdb_lookup.is_some() && custom_lookup.is_some() {
         err::error(err::ERR_ARGPARSE, Some("Both afdb_lookup and custom_lookup are specified. Please specify only one.".to_string()));
@@ -53,7 +342,24 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
 
     // Check if the checkpoint file exists
     let checkpoint_file = format!("{}/createdb.chk", parent);
-    if Path::new(&checkpoint_file).exists() {
+    // Finish an append or removal interrupted while swapping its staged files into the database
+    let swap_marker = format!("{}/swap.chk", parent);
+    if finish_staged(&output, &swap_marker, &checkpoint_file)?.as_deref() == Some("append") && append {
+        msg::println_message(&"The species of the interrupted run are appended; run createdb --append again to add other species".to_string(), 3);
+        return Ok(());
+    }
+    if append {
+        // New species are added to a finished database
+        if !Path::new(&checkpoint_file).exists() || chkpnt::read_checkpoint(&checkpoint_file)? != "1" || !Path::new(&format!("{}.map", output)).exists() {
+            err::error(err::ERR_GENERAL, Some(format!("No finished database found at {} to append to", output)));
+        }
+        // Files of an append interrupted before the swap are left over; the database itself is intact
+        remove_staged(&output)?;
+        for ext in [".map", ".provenance.tsv", ".meta"] {
+            let file = format!("{}{}.new", output, ext);
+            if Path::new(&file).exists() { std::fs::remove_file(&file)?; }
+        }
+    } else if Path::new(&checkpoint_file).exists() {
         // Read the checkpoint file
         let content = chkpnt::read_checkpoint(&checkpoint_file)?;
         if content == "1" && !overwrite {
@@ -63,54 +369,58 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
         // Write the checkpoint file
         chkpnt::write_checkpoint(&checkpoint_file, "0")?;
     }
-    
-    // Get all the fasta files in input directory
-    let mut fasta_files = Vec::new();
-    if Path::new(&input).is_dir() {
-        for entry in std::fs::read_dir(&input)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() && (path.extension().unwrap_or_default() == "fasta" || path.extension().unwrap_or_default() == "fa") {
-                fasta_files.push(path.clone().to_string_lossy().into_owned());
-            }
+
+    // Make sure the lookup tables are available before reading the inputs; without them, all sequences are predicted
+    let afdb_lookup = match afdb_lookup {
+        Some(path) => {
+            let (policy, source) = afdb_lookup::download_policy(lookup_download).unwrap_or_else(|e| { err::error(err::ERR_ARGPARSE, Some(e)); });
+            msg::println_message(&format!("Lookup table download policy: {} ({})", policy.name(), source), 3);
+            if afdb_lookup::prepare_tables(&path, &afdb_lookup::base_url(afdb_url), policy)? { Some(path) } else { None }
+        },
+        None => None,
+    };
+    let cache = match prediction_cache::cache_dir(cache) {
+        Some(dir) => {
+            msg::println_message(&format!("Using the prediction cache in {}", dir), 3);
+            Some(PredictionCache::open(&dir)?)
+        },
+        None => None,
+    };
+
+    // Get all the proteomes in input directory or sample sheet
+    let mut proteomes = proteome::collect_proteomes(&input)?;
+    if let Some(dir) = &precomputed_3di {
+        proteome::assign_precomputed_3di(&mut proteomes, dir)?;
+    }
+
+    // Write out the species metadata given in the sample sheet
+    // The rows of new species are added to the existing metadata only after the database is updated
+    let meta_file = format!("{}.meta", output);
+    let new_meta_file = if append { format!("{}.new", meta_file) } else { meta_file.clone() };
+    if let Some(first) = proteomes.iter().find(|p| !p.metadata.is_empty()) {
+        let meta_exists = append && Path::new(&meta_file).exists();
+        let mut meta_writer = BufWriter::new(std::fs::File::create(&new_meta_file)?);
+        let columns = first.metadata.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+        if !meta_exists { writeln!(meta_writer, "species\t{}", columns.join("\t"))?; }
+        for proteome in &proteomes {
+            let values = proteome.metadata.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>();
+            writeln!(meta_writer, "{}\t{}", proteome.species, values.join("\t"))?;
         }
-    } else {
-        let path = Path::new(&input);
-        if !path.is_file() { err::error(err::ERR_GENERAL, Some("Input is not a directory or a file".to_string())); }
-        fasta_files.push(path.to_string_lossy().into_owned());
+        meta_writer.flush()?;
     }
 
-    // Read in the fasta files
-    // In the same time, write out the mapping file from gene to species (file name)
     // Generate gene origin mapping file
+    // New rows are appended to the existing mapping file only after the database is updated
     let mapping_file = format!("{}.map", output);
-    let mut mapping_writer = BufWriter::new(std::fs::File::create(&mapping_file)?);
-    let mut fasta_data = HashMap::new();
-    for file in fasta_files {
-        let species = Path::new(&file).file_stem().unwrap().to_str().unwrap();
-        let each_fasta = fasta::read_fasta(&file);
-        for (key, value) in each_fasta {
-            if let Some(max_len) = max_len {
-                if value.len() > max_len { continue; }
-            }
-            if value.len() < 2 {
-                msg::println_message(&format!("Skipping {} as it is too short", key), 3);
-                continue;
-            }
-            // replace all whitespace characters with underscore
-            let key = key.replace(|c: char| need_replacement(c), "_");
+    let new_mapping_file = if append { format!("{}.new", mapping_file) } else { mapping_file.clone() };
+    // Source of the 3Di sequence of each entry: (name, source, accession, pLDDT)
+    let provenance_file = format!("{}.provenance.tsv", output);
+    let provenance_exists = append && Path::new(&provenance_file).exists();
+    let new_provenance_file = if append { format!("{}.new", provenance_file) } else { provenance_file.clone() };
+    // Tables of the new species to merge into those of the database when appending, as (new, existing)
+    let new_tables = [(new_mapping_file.clone(), mapping_file.clone()), (new_provenance_file.clone(), provenance_file.clone()), (new_meta_file.clone(), meta_file.clone())];
 
-            // hash sequence to avoid name collision
-            let mut hash = format!("{:x}", md5::compute(value.clone()));
-            hash.truncate(10);
-            let hashed_name = format!("unicore_{}", hash);
-            fasta_data.insert(hashed_name.clone(), value);
-            writeln!(mapping_writer, "{}\t{}\t{}", hashed_name, species, key)?;
-        }
-    }
-    mapping_writer.flush()?;
-
-    // Write out the combined amino acid fasta file into output directory
+    // Intermediate files are written into the output directory
     // If 'parent' is absolute path, make curr_dir to the parent directory of the 'parent'
     let curr_dir = if Path::new(&parent).is_absolute() {
         if let Some(p) = Path::new(&parent).parent() {
@@ -121,87 +431,413 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
     } else {
         var::current_dir()
     };
-
     let combined_aa = format!("{}{}{}{}combined_aa.fasta", curr_dir, SEP, parent, SEP);
-    let converted_aa = format!("{}{}{}{}converted_aa.fasta", curr_dir, SEP, parent, SEP);
-    let converted_ss = format!("{}{}{}{}converted_ss.fasta", curr_dir, SEP, parent, SEP);
-    if afdb_lookup.is_some() {
-        // this will split data into converted and combined fasta files
-        crate::seq::afdb_lookup::run_afdb(&fasta_data, &afdb_lookup.clone().unwrap(), &converted_aa, &converted_ss, &combined_aa)?;
-    } else if custom_lookup.is_some() {
-        crate::seq::afdb_lookup::run_custom(&fasta_data, &custom_lookup.clone().unwrap(), &converted_aa, &converted_ss, &combined_aa)?;
-    } else {
-        fasta::write_fasta(&combined_aa, &fasta_data, false)?;
+    let converted_db = format!("{}{}{}{}converted", curr_dir, SEP, parent, SEP);
+    let bucket_dir = format!("{}{}buckets", parent, SEP);
+    let lookup_bucket_dir = format!("{}{}lookup_buckets", parent, SEP);
+
+    // Sequences are streamed into buckets split by their hash, so that only one bucket is held in memory at a time
+    // Each sequence is validated first, and the dropped or changed ones are listed in the report
+    let report_file = format!("{}.validation.tsv", output);
+    let mut stream = RecordStream {
+        buckets: bucket::Buckets::create(&bucket_dir)?,
+        mapping_writer: BufWriter::new(std::fs::File::create(&new_mapping_file)?),
+        rows: 0,
+        existing_species: HashSet::new(),
+        rules: validate::Rules { min_len, max_len, max_ambiguous },
+        report: BufWriter::new(std::fs::File::create(&report_file)?),
+        report_file: report_file.clone(),
+        read: 0,
+        dropped: 0,
+        changed: 0,
+        nucleotide: 0,
+        nucleotide_letters: 0,
+    };
+    writeln!(stream.report, "file\tspecies\tgene\taction\treason")?;
+
+    // Load the species and sequences already in the database
+    if append {
+        let reader = BufReader::new(std::fs::File::open(&mapping_file)?);
+        for line in reader.lines() {
+            if let Some(species) = line?.split('\t').nth(1) { stream.existing_species.insert(species.to_string()); }
+        }
+        let names = DbReader::open(&format!("{}_h", output))?;
+        let seqs = DbReader::open(&output)?;
+        for key in names.keys() {
+            let name = header_name(&names.get_str(key).unwrap_or_default());
+            let Some(seq) = seqs.get_str(key) else {
+                err::error(err::ERR_GENERAL, Some(format!("Entry {} of the database has no amino acid sequence", name)));
+            };
+            // Bucketed by the MD5 of the sequence, as the names of older databases carry a shorter hash
+            let hash = format!("{:x}", md5::compute(seq.as_bytes()));
+            stream.buckets.write(bucket::index(&hash), &[&name, &name, &seq, "", "existing", "-", "-", "-"])?;
+        }
+        msg::println_message(&format!("Appending to the database of {} species and {} sequences", stream.existing_species.len(), names.len()), 3);
     }
 
+    // Read in the proteomes
+    // In the same time, write out the mapping file from gene to species (file name)
+    let mut inputs: Vec<InputSet> = proteomes.into_iter().map(InputSet::Proteome).collect();
+    if let Some(dir) = &structures {
+        inputs.extend(collect_structures(dir)?);
+    }
+    for input in inputs {
+        match input {
+            InputSet::Proteome(proteome) => {
+                msg::println_message(&format!("Reading {} from {}", proteome.species, proteome.file()), 4);
+                let mut each_ss = match &proteome.ss {
+                    Some(file) => {
+                        msg::println_message(&format!("Reading precomputed 3Di sequences of {} from {}", proteome.species, file), 4);
+                        fasta::read_fasta(file)?.into_iter()
+                            .map(|(h, s)| (h.split_whitespace().next().unwrap_or("").to_string(), s))
+                            .collect()
+                    },
+                    None => HashMap::new(),
+                };
+                let mut unmatched = 0;
+                for record in proteome.records(genetic_code)? {
+                    let (key, value) = record?;
+                    // 3Di sequences are paired by the first word of the header
+                    let ss = each_ss.remove(key.split_whitespace().next().unwrap_or(""));
+                    // Take the species label from the header if requested
+                    let (species, key) = match &header_species {
+                        Some(rule) => match rule.split(&key) {
+                            Some(split) => split,
+                            None => { unmatched += 1; continue; },
+                        },
+                        None => (proteome.species.clone(), key),
+                    };
+                    stream.add(proteome.file(), species, key, value, ss, "sequence")?;
+                }
+                stream.finish_file(proteome.file());
+                if proteome.ss.is_some() && !each_ss.is_empty() {
+                    err::warning(err::WRN_GENERAL, Some(format!("{} 3Di sequences of {} have no matching amino acid sequence", each_ss.len(), proteome.species)));
+                }
+                if unmatched > 0 {
+                    err::warning(err::WRN_GENERAL, Some(format!("{} sequences in {} have no species label in their headers; skipping", unmatched, proteome.file())));
+                }
+            },
+            InputSet::Structures { species, dir } => {
+                msg::println_message(&format!("Reading structures of {} from {}", species, dir), 4);
+                let foldseek_path = match &bin.get("foldseek") {
+                    Some(bin) => &bin.path,
+                    _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
+                };
+                let structure_db = format!("{}{}structure_db", parent, SEP);
+                cmd::run(Cmd::new(foldseek_path).arg("createdb").arg(&dir).arg(&structure_db).arg("--threads").arg(threads.to_string()).arg("-v").arg(foldseek_verbosity.as_str()));
+                let names = DbReader::open(&format!("{}_h", structure_db))?;
+                let aa_seqs = DbReader::open(&structure_db)?;
+                let ss_seqs = DbReader::open(&format!("{}_ss", structure_db))?;
+                for key in names.keys() {
+                    let name = header_name(&names.get_str(key).unwrap_or_default());
+                    match (aa_seqs.get_str(key), ss_seqs.get_str(key)) {
+                        (Some(aa), Some(ss)) => stream.add(&dir, species.clone(), name, aa, Some(ss), "structure")?,
+                        _ => err::error(err::ERR_GENERAL, Some(format!("Structure {} in {} has no amino acid or 3di sequence", name, dir))),
+                    }
+                }
+                stream.finish_file(&dir);
+                for suffix in ["", "_h", "_ss", "_ca"] {
+                    cmd::run(Cmd::new(foldseek_path).arg("rmdb").arg(format!("{}{}", structure_db, suffix)).arg("-v").arg(foldseek_verbosity.as_str()));
+                }
+            },
+        }
+    }
+    let RecordStream { buckets, mut mapping_writer, mut report, .. } = stream;
+    mapping_writer.flush()?;
+    report.flush()?;
+    let counts = buckets.finish()?;
+
+    // Name the sequences in each bucket and route the new ones by how their 3Di sequences are obtained
+    let mut combined_writer = BufWriter::new(std::fs::File::create(&combined_aa)?);
+    let mut converted_writer = SeqDbWriter::create(&converted_db)?;
+    let mut provenance_writer = BufWriter::new(std::fs::File::create(&new_provenance_file)?);
+    if !provenance_exists { writeln!(provenance_writer, "name\tsource\taccession\tplddt")?; }
+    let custom_table = match &custom_lookup {
+        Some(path) => Some(afdb_lookup::load_custom(path)?),
+        None => None,
+    };
+    let mut lookup_buckets = match &afdb_lookup {
+        Some(_) => Some(bucket::Buckets::create(&lookup_bucket_dir)?),
+        None => None,
+    };
+    let (mut shared, mut unique, mut new_unique, mut precomputed, mut combined) = (0, 0, 0, 0, 0);
+    // Renamed rows of the mapping file
+    let mut renames: HashMap<usize, String> = HashMap::new();
+    for (i, &count) in counts.iter().enumerate() {
+        if count == 0 { continue; }
+        let mut entries: Vec<BucketEntry> = Vec::new();
+        let mut names: HashMap<String, usize> = HashMap::new();
+        // Entries of the database by their sequences
+        let mut existing: HashMap<String, usize> = HashMap::new();
+        for record in bucket::read_bucket(&bucket_dir, i)? {
+            let (base, seq, kind) = (&record[0], &record[2], record[4].as_str());
+            if kind == "existing" {
+                names.insert(record[1].clone(), entries.len());
+                existing.insert(seq.clone(), entries.len());
+                entries.push((record[1].clone(), seq.clone(), None, false));
+                continue;
+            }
+            // Sequences already in the database keep their names there, whatever hash they were named by
+            if let Some(&j) = existing.get(seq) {
+                shared += 1;
+                if entries[j].0 != *base {
+                    renames.insert(record[5].parse()?, entries[j].0.clone());
+                }
+                continue;
+            }
+            // Identical sequences share one entry; a different sequence with the same hash is disambiguated with a suffix
+            let mut name = base.clone();
+            let mut suffix = 1;
+            let index = loop {
+                match names.get(&name) {
+                    Some(&j) if entries[j].1 == *seq => { shared += 1; break j; },
+                    Some(_) => {
+                        suffix += 1;
+                        name = format!("{}_{}", base, suffix);
+                    },
+                    None => {
+                        if suffix > 1 {
+                            err::warning(err::WRN_GENERAL, Some(format!("Hash collision of {} ({} from {}); renamed to {}", base, record[7], record[6], name)));
+                        }
+                        names.insert(name.clone(), entries.len());
+                        entries.push((name.clone(), seq.clone(), None, true));
+                        break entries.len() - 1;
+                    },
+                }
+            };
+            if name != *base {
+                renames.insert(record[5].parse()?, name);
+            }
+            // 3Di sequences from structures take precedence over the precomputed ones
+            if !record[3].is_empty() && (kind == "structure" || entries[index].2.is_none()) {
+                let source = if kind == "structure" { "structure" } else { "precomputed" };
+                entries[index].2 = Some((record[3].clone(), source));
+            }
+        }
+        unique += entries.len();
+
+        // New entries are written in the order of their names
+        let mut new_entries = entries.into_iter().filter(|e| e.3).collect::<Vec<_>>();
+        new_entries.sort_by(|a, b| a.0.cmp(&b.0));
+        new_unique += new_entries.len();
+        for (name, seq, ss, _) in new_entries {
+            if let Some((ss, source)) = ss {
+                // Sequences with precomputed 3Di sequences are not predicted
+                converted_writer.add(&name, &seq, &ss)?;
+                writeln!(provenance_writer, "{}\t{}\t-\t-", name, source)?;
+                precomputed += 1;
+            } else if let Some(lookup_buckets) = lookup_buckets.as_mut() {
+                let hash = afdb_lookup::table_hash(&seq);
+                lookup_buckets.write(bucket::index(&hash), &[&name, &seq, &hash])?;
+            } else if let Some(table) = &custom_table {
+                match table.get(&seq) {
+                    Some(entry) => {
+                        converted_writer.add(&name, &seq, &entry.ss)?;
+                        writeln!(provenance_writer, "{}\tcustom\t{}\t-", name, entry.accession.as_deref().unwrap_or("-"))?;
+                    },
+                    None => {
+                        writeln!(combined_writer, ">{}\n{}", name, seq)?;
+                        combined += 1;
+                    },
+                }
+            } else {
+                writeln!(combined_writer, ">{}\n{}", name, seq)?;
+                combined += 1;
+            }
+        }
+    }
+    if shared > 0 {
+        msg::println_message(&format!("{} genes share identical sequences with other genes; {} unique sequences are stored", shared, unique), 3);
+    }
+    if precomputed > 0 {
+        msg::println_message(&format!("{} sequences have precomputed 3Di sequences", precomputed), 3);
+    }
+    if custom_table.is_some() {
+        msg::println_message(&format!("{} sequences found from the lookup database", converted_writer.len() - precomputed), 3);
+        msg::println_message(&format!("{} sequences not found and will be predicted", combined), 3);
+    }
+    // this will split the remaining sequences into the converted database and combined fasta file
+    if let (Some(lookup_buckets), Some(afdb_lookup)) = (lookup_buckets, &afdb_lookup) {
+        let counts = lookup_buckets.finish()?;
+        afdb_lookup::run_afdb(&lookup_bucket_dir, &counts, afdb_lookup, min_plddt, &mut converted_writer, &mut combined_writer, &mut provenance_writer)?;
+    }
+    combined_writer.flush()?;
+    drop(combined_writer);
+    let combined = apply_cache(&combined_aa, cache.as_ref(), &mut converted_writer, &mut provenance_writer)?;
+    provenance_writer.flush()?;
+    let converted = converted_writer.len();
+    converted_writer.finish()?;
+
+    // Rename the rows of the mapping file whose sequences collided with others
+    if !renames.is_empty() {
+        let renamed_file = format!("{}.renamed", new_mapping_file);
+        let mut writer = BufWriter::new(std::fs::File::create(&renamed_file)?);
+        for (row, line) in BufReader::new(std::fs::File::open(&new_mapping_file)?).lines().enumerate() {
+            let line = line?;
+            match (renames.get(&row), line.split_once('\t')) {
+                (Some(name), Some((_, rest))) => writeln!(writer, "{}\t{}", name, rest)?,
+                _ => writeln!(writer, "{}", line)?,
+            }
+        }
+        writer.flush()?;
+        std::fs::rename(&renamed_file, &new_mapping_file)?;
+    }
+
+    // Delete the buckets
+    if !keep {
+        std::fs::remove_dir_all(&bucket_dir)?;
+        if afdb_lookup.is_some() { std::fs::remove_dir_all(&lookup_bucket_dir)?; }
+    }
+
+    // Only the sequences not in the database yet are predicted when appending
+    if append {
+        msg::println_message(&format!("{} new sequences to add to the database", new_unique), 3);
+        if new_unique == 0 {
+            std::fs::remove_file(&combined_aa)?;
+            for suffix in ["", "_ss", "_h"] {
+                foldseek_db::remove_db(&format!("{}{}", converted_db, suffix))?;
+            }
+            commit_append(&output, &new_tables, &swap_marker, &checkpoint_file)?;
+            return Ok(());
+        }
+    }
+
+    // Database to be created; merged into the existing database later when appending
+    let db = if append { format!("{}{}appended_db", parent, SEP) } else { output.clone() };
+
+    // ProstT5 is run only if any sequence is left to predict
+    let predict = combined > 0;
+    let has_converted = converted > 0;
+
     // Use foldseek to create the database
     let foldseek_path = match &bin.get("foldseek") {
         Some(bin) => &bin.path,
         _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
     };
 
-    // Check if old weights exist
-    if Path::new(&model).join("cnn.safetensors").exists() || Path::new(&model).join(format!("model{}cnn.safetensors", SEP)).exists() {
-        err::error(err::ERR_GENERAL, Some("Old weight files detected from the given path. Please provide different path for the model weights".to_string()));
-    }
-    // Check if weights exist
-    if !Path::new(&model).join("prostt5-f16.gguf").exists() {
-        // Download the model
-        std::fs::create_dir_all(format!("{}{}tmp", model, SEP))?;
-        let mut cmd = std::process::Command::new(foldseek_path);
-        let mut cmd = cmd
-            .arg("databases").arg("ProstT5").arg(&model).arg(format!("{}{}tmp", model, SEP)).arg("--threads").arg(threads.to_string());
-        cmd::run(&mut cmd);
-    }
-
-    // Run foldseek createdb
-    let mut cmd = std::process::Command::new(foldseek_path);
-    let cmd = cmd
-        .arg("createdb").arg(&combined_aa).arg(&output)
-        .arg("--prostt5-model").arg(&model)
-        .arg("--threads").arg(threads.to_string());
-    let mut cmd = if gpu {
-        cmd.arg("--gpu").arg("1")
-    } else { cmd };
-    cmd::run(&mut cmd);
-
-    if afdb_lookup.is_some() || custom_lookup.is_some() {
-        let foldseek_path = match &bin.get("foldseek") {
-            Some(bin) => &bin.path,
-            _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
-        };
-        let converted_aa_db = format!("{}{}{}{}converted", curr_dir, SEP, parent, SEP);
-        let converted_h_db = format!("{}{}{}{}converted_h", curr_dir, SEP, parent, SEP);
-        let converted_ss_db = format!("{}{}{}{}converted_ss", curr_dir, SEP, parent, SEP);
-        let converted_ss_h_db = format!("{}{}{}{}converted_ss_h", curr_dir, SEP, parent, SEP);
-        cmd::run(Cmd::new(foldseek_path).arg("base:createdb").arg(&converted_aa).arg(&converted_aa_db).arg("--shuffle").arg("0").arg("-v").arg(foldseek_verbosity.as_str()));
-        cmd::run(Cmd::new(foldseek_path).arg("base:createdb").arg(&converted_ss).arg(&converted_ss_db).arg("--shuffle").arg("0").arg("-v").arg(foldseek_verbosity.as_str()));
-
-        // Concatenate the two databases
-        let output_ss = format!("{}_ss", output);
-        let output_h = format!("{}_h", output);
-        let concat_aa_db = format!("{}{}{}{}concat_aa", curr_dir, SEP, parent, SEP);
-        let concat_ss_db = format!("{}{}{}{}concat_ss", curr_dir, SEP, parent, SEP);
-        let concat_h_db = format!("{}{}{}{}concat_h", curr_dir, SEP, parent, SEP);
-        cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&output).arg(&converted_aa_db).arg(&concat_aa_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
-        cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&output_ss).arg(&converted_ss_db).arg(&concat_ss_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
-        cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&output_h).arg(&converted_h_db).arg(&concat_h_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
-
-        // Rename databases
-        cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_aa_db).arg(&output).arg("-v").arg(foldseek_verbosity.as_str()));
-        cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_ss_db).arg(&output_ss).arg("-v").arg(foldseek_verbosity.as_str()));
-        cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_h_db).arg(&output_h).arg("-v").arg(foldseek_verbosity.as_str()));
+    if predict {
+        // Check if old weights exist
+        if Path::new(&model).join("cnn.safetensors").exists() || Path::new(&model).join(format!("model{}cnn.safetensors", SEP)).exists() {
+            err::error(err::ERR_GENERAL, Some("Old weight files detected from the given path. Please provide different path for the model weights".to_string()));
+        }
+        // Check if weights exist
+        if !Path::new(&model).join("prostt5-f16.gguf").exists() {
+            // Download the model
+            std::fs::create_dir_all(format!("{}{}tmp", model, SEP))?;
+            let mut cmd = std::process::Command::new(foldseek_path);
+            let cmd = cmd
+                .arg("databases").arg("ProstT5").arg(&model).arg(format!("{}{}tmp", model, SEP)).arg("--threads").arg(threads.to_string());
+            cmd::run(cmd);
+        }
 
-        // Delete intermediate files
+        // Predict the 3Di sequences shard by shard; finished shards are skipped when resuming
+        let shard_dir = format!("{}{}shards", parent, SEP);
+        let shards = split_shards(&combined_aa, &shard_dir, shard_size)?;
+        for i in 0..shards {
+            let (shard_fasta, shard_db, shard_chk) = shard_files(&shard_dir, i);
+            if Path::new(&shard_chk).exists() && chkpnt::read_checkpoint(&shard_chk)? == "1" && Path::new(&shard_db).exists() {
+                msg::println_message(&format!("Shard {}/{} already predicted, skipping", i + 1, shards), 3);
+                continue;
+            }
+            msg::println_message(&format!("Predicting 3Di sequences of shard {}/{}...", i + 1, shards), 3);
+            chkpnt::write_checkpoint(&shard_chk, "0")?;
+            let mut cmd = std::process::Command::new(foldseek_path);
+            let cmd = cmd
+                .arg("createdb").arg(&shard_fasta).arg(&shard_db)
+                .arg("--prostt5-model").arg(&model)
+                .arg("--threads").arg(threads.to_string());
+            let cmd = if gpu {
+                cmd.arg("--gpu").arg("1")
+            } else { cmd };
+            cmd::run(cmd);
+            // A failure to update the cache does not affect the database
+            if let Some(cache) = &cache {
+                match cache_shard(cache, &shard_db) {
+                    Ok(count) => msg::println_message(&format!("{} predicted 3Di sequences added to the cache", count), 4),
+                    Err(e) => err::warning(err::WRN_GENERAL, Some(format!("Could not add the predictions of shard {} to the cache: {}", i + 1, e))),
+                }
+            }
+            chkpnt::write_checkpoint(&shard_chk, "1")?;
+        }
+
+        // Merge the shards into the database
+        let mut merged = shard_files(&shard_dir, 0).1;
+        for i in 1..shards {
+            let shard_db = shard_files(&shard_dir, i).1;
+            let next = format!("{}{}merged_{}", shard_dir, SEP, i);
+            for suffix in ["", "_ss", "_h"] {
+                cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(format!("{}{}", merged, suffix)).arg(format!("{}{}", shard_db, suffix)).arg(format!("{}{}", next, suffix)).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
+                if i > 1 {
+                    cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(format!("{}{}", merged, suffix)).arg("-v").arg(foldseek_verbosity.as_str()));
+                }
+            }
+            merged = next;
+        }
+        for suffix in ["", "_ss", "_h"] {
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(format!("{}{}", merged, suffix)).arg(format!("{}{}", db, suffix)).arg("-v").arg(foldseek_verbosity.as_str()));
+        }
         if !keep {
-            std::fs::remove_file(converted_aa)?;
-            std::fs::remove_file(converted_ss)?;
+            std::fs::remove_dir_all(&shard_dir)?;
+        }
+    }
+
+    if has_converted {
+        let converted_aa_db = converted_db.clone();
+        let converted_h_db = format!("{}_h", converted_db);
+        let converted_ss_db = format!("{}_ss", converted_db);
+
+        let output_ss = format!("{}_ss", db);
+        let output_h = format!("{}_h", db);
+        if predict {
+            // Concatenate the two databases
+            let concat_aa_db = format!("{}{}{}{}concat_aa", curr_dir, SEP, parent, SEP);
+            let concat_ss_db = format!("{}{}{}{}concat_ss", curr_dir, SEP, parent, SEP);
+            let concat_h_db = format!("{}{}{}{}concat_h", curr_dir, SEP, parent, SEP);
+            cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&db).arg(&converted_aa_db).arg(&concat_aa_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
+            cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&output_ss).arg(&converted_ss_db).arg(&concat_ss_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
+            cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&output_h).arg(&converted_h_db).arg(&concat_h_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
+
+            // Rename databases
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_aa_db).arg(&db).arg("-v").arg(foldseek_verbosity.as_str()));
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_ss_db).arg(&output_ss).arg("-v").arg(foldseek_verbosity.as_str()));
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&concat_h_db).arg(&output_h).arg("-v").arg(foldseek_verbosity.as_str()));
             std::fs::remove_file(format!("{}.source", concat_aa_db)).or_else(|_| Ok::<(), Box<dyn std::error::Error>>(()))?;
+        } else {
+            // Nothing was predicted; the converted databases become the output
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&converted_aa_db).arg(&db).arg("-v").arg(foldseek_verbosity.as_str()));
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&converted_ss_db).arg(&output_ss).arg("-v").arg(foldseek_verbosity.as_str()));
+            cmd::run(Cmd::new(foldseek_path).arg("base:mvdb").arg(&converted_h_db).arg(&output_h).arg("-v").arg(foldseek_verbosity.as_str()));
+        }
+
+        // Delete intermediate files
+        if !keep && predict {
             cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(&converted_aa_db).arg("-v").arg(foldseek_verbosity.as_str()));
             cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(&converted_h_db).arg("-v").arg(foldseek_verbosity.as_str()));
             cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(&converted_ss_db).arg("-v").arg(foldseek_verbosity.as_str()));
-            cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(&converted_ss_h_db).arg("-v").arg(foldseek_verbosity.as_str()));
         }
+    } else {
+        // The converted database is empty
+        for suffix in ["", "_ss", "_h"] {
+            foldseek_db::remove_db(&format!("{}{}", converted_db, suffix))?;
+        }
+    }
+
+    // Merge the new entries into the existing database
+    // The lookup files of the merged databases are written from their headers, as concatdbs doesn't update them
+    if !append {
+        foldseek_db::write_lookup(&db)?;
+    } else {
+        let staged_db = format!("{}{}", output, STAGED);
+        for suffix in ["", "_ss", "_h"] {
+            let (existing_db, new_db, staged_db) = (format!("{}{}", output, suffix), format!("{}{}", db, suffix), format!("{}{}", staged_db, suffix));
+            cmd::run(Cmd::new(foldseek_path).arg("base:concatdbs").arg(&existing_db).arg(&new_db).arg(&staged_db).arg("-v").arg(foldseek_verbosity.as_str()).arg("--threads").arg("1"));
+        }
+        std::fs::remove_file(format!("{}.source", staged_db)).or_else(|_| Ok::<(), Box<dyn std::error::Error>>(()))?;
+        foldseek_db::write_lookup(&staged_db)?;
+        for suffix in ["", "_ss", "_h"] {
+            cmd::run(Cmd::new(foldseek_path).arg("base:rmdb").arg(format!("{}{}", db, suffix)).arg("-v").arg(foldseek_verbosity.as_str()));
+        }
+        commit_append(&output, &new_tables, &swap_marker, &checkpoint_file)?;
     }
 
     // Delete intermediate files
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, MAIN_SEPARATOR as SEP};
+
+use crate::envs::error_handler as err;
+use crate::envs::variables as var;
+use crate::modules::profile::gene_name;
+use crate::seq::cluster_diff::read_clusters;
+use crate::seq::foldseek_db::{open_by_name, DbReader, SeqDbWriter};
+use crate::util::arg_parser::Args;
+use crate::util::checkpoint as chkpnt;
+use crate::util::message as msg;
+
+// Names of the core genes in a profile output directory
+fn core_genes(profile_dir: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut genes: Vec<String> = std::fs::read_dir(profile_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "txt"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    genes.sort();
+    Ok(genes)
+}
+
+// Export the core genes of an easy-core output as a reference database for search and easy-search
+pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
+    // Retrieve arguments
+    let input = args.exportcore_input.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("export-core - input".to_string())); });
+    let output = args.exportcore_output.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("export-core - output".to_string())); });
+
+    // Outputs of the easy-core modules
+    let db = format!("{}{}proteome{}proteome_db", input, SEP, SEP);
+    let cluster_tsv = format!("{}{}cluster{}clust.tsv", input, SEP, SEP);
+    let profile_dir = format!("{}{}profile", input, SEP);
+    let profile_chk = format!("{}{}profile.chk", profile_dir, SEP);
+    if !Path::new(&profile_chk).exists() || chkpnt::read_checkpoint(&profile_chk)? != "1" {
+        err::error(err::ERR_GENERAL, Some(format!("No finished profile found in {}; run easy-core first", profile_dir)));
+    }
+    for file in [format!("{}.index", db), cluster_tsv.clone()] {
+        if !Path::new(&file).exists() {
+            err::error(err::ERR_GENERAL, Some(format!("{} does not exist", file)));
+        }
+    }
+
+    // Output directory
+    if let Some(parent) = Path::new(&output).parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Clusters of the core genes, by the names of their files
+    let genes = core_genes(&profile_dir)?;
+    if genes.is_empty() {
+        err::error(err::ERR_GENERAL, Some(format!("No core genes found in {}", profile_dir)));
+    }
+    let clusters: HashMap<String, (String, usize)> = read_clusters(&cluster_tsv)?.into_iter()
+        .map(|(rep, members)| (gene_name(&rep).to_string(), (rep, members.len())))
+        .collect();
+    // Copy number statistics, if profile wrote them
+    let mut copiness: HashMap<String, (String, String)> = HashMap::new();
+    let copiness_file = format!("{}{}copiness.tsv", profile_dir, SEP);
+    if Path::new(&copiness_file).exists() {
+        for line in BufReader::new(File::open(&copiness_file)?).lines().skip(1) {
+            let line = line?;
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 3 { continue; }
+            copiness.insert(gene_name(cols[0]).to_string(), (cols[2].to_string(), cols[1].to_string()));
+        }
+    }
+
+    // Write the representative sequences of the core genes, and their cluster metadata
+    let aa = open_by_name(&db)?;
+    let ss = DbReader::open(&format!("{}_ss", db))?;
+    let mut writer = SeqDbWriter::create(&output)?;
+    let mut metadata = BufWriter::new(File::create(format!("{}.core.tsv", output))?);
+    writeln!(metadata, "gene\trepresentative\tcluster_size\tsingle_copy_species\tsingle_copy_percent\tmultiple_copy_percent")?;
+    for gene in &genes {
+        let Some((rep, cluster_size)) = clusters.get(gene) else {
+            err::warning(err::WRN_GENERAL, Some(format!("Core gene {} is not in {}; skipped", gene, cluster_tsv)));
+            continue;
+        };
+        let sequences = aa.key_of(rep).and_then(|key| Some((aa.get_str(key)?, ss.get_str(key)?)));
+        let Some((aa_seq, ss_seq)) = sequences else {
+            err::warning(err::WRN_GENERAL, Some(format!("Representative {} of core gene {} is not in {}; skipped", rep, gene, db)));
+            continue;
+        };
+        writer.add(rep, &aa_seq, &ss_seq)?;
+
+        let species = BufReader::new(File::open(format!("{}{}{}.txt", profile_dir, SEP, gene))?).lines().count();
+        let (single, multiple) = copiness.get(gene).cloned().unwrap_or(("-".to_string(), "-".to_string()));
+        writeln!(metadata, "{}\t{}\t{}\t{}\t{}\t{}", gene, rep, cluster_size, species, single, multiple)?;
+    }
+    metadata.flush()?;
+    let exported = writer.len();
+    writer.finish()?;
+
+    msg::println_message(&format!("Exported {} of {} core genes to {}", exported, genes.len(), output), 3);
+    Ok(())
+}
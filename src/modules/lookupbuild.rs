@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, MAIN_SEPARATOR as SEP};
+
+use crate::envs::error_handler as err;
+use crate::envs::variables as var;
+use crate::seq::afdb_lookup;
+use crate::seq::bucket;
+use crate::seq::foldseek_db::{header_name, DbReader, DBTYPE_AMINO_ACIDS};
+use crate::seq::lookup_index;
+use crate::util::arg_parser::Args;
+use crate::util::message as msg;
+
+pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
+    // Retrieve arguments
+    let sources = args.lookupbuild_sources.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("lookup-build - sources".to_string())); });
+    let output = args.lookupbuild_output.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("lookup-build - output".to_string())); });
+    let append = args.lookupbuild_append.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("lookup-build - append".to_string())); });
+
+    // Tables are written in the same layout as the downloaded AFDB tables
+    let dir = afdb_lookup::table_dir(&output);
+    let exists = (0..bucket::BUCKETS).any(|i| Path::new(&afdb_lookup::table_file(&dir, i)).exists());
+    if exists && !append {
+        err::error(err::ERR_GENERAL, Some(format!("Lookup tables already exist in {}. Use --append to add entries to them", dir)));
+    }
+    if append && exists && !afdb_lookup::tables_complete(&dir) {
+        err::error(err::ERR_GENERAL, Some(format!("Lookup tables in {} are incomplete", dir)));
+    }
+    std::fs::create_dir_all(&dir)?;
+
+    // Stream the entries of the sources into buckets by the MD5 hash of the amino acid sequences
+    // The first word of the header is recorded as the accession if the source has headers
+    let bucket_dir = format!("{}{}buckets", dir, SEP);
+    let mut buckets = bucket::Buckets::create(&bucket_dir)?;
+    for source in &sources {
+        let aa = DbReader::open(source)?;
+        let ss = DbReader::open(&format!("{}_ss", source))?;
+        if aa.dbtype() != DBTYPE_AMINO_ACIDS || ss.dbtype() != DBTYPE_AMINO_ACIDS {
+            err::error(err::ERR_GENERAL, Some(format!("{} is not a Foldseek sequence database with 3Di sequences", source)));
+        }
+        let headers = DbReader::open(&format!("{}_h", source)).ok();
+        let mut count = 0;
+        for key in aa.keys() {
+            let (Some(aa_seq), Some(ss_seq)) = (aa.get_str(key), ss.get_str(key)) else { continue; };
+            if aa_seq.len() != ss_seq.len() {
+                err::warning(err::WRN_GENERAL, Some(format!("Entry {} of {} has amino acid and 3Di sequences of different lengths; skipping", key, source)));
+                continue;
+            }
+            let hash = afdb_lookup::table_hash(&aa_seq);
+            let accession = headers.as_ref().and_then(|h| h.get_str(key)).map(|h| header_name(&h)).unwrap_or_default();
+            buckets.write(bucket::index(&hash), &[&hash, &ss_seq, &accession])?;
+            count += 1;
+        }
+        msg::println_message(&format!("Read {} entries from {}", count, source), 3);
+    }
+    buckets.finish()?;
+
+    // Merge the buckets into the tables; entries already in the tables are kept over the new ones
+    // A hash duplicated within a table keeps its last entry, as in the lookups
+    // Entries are kept as the columns after the hash, so accessions and pLDDTs of existing tables are preserved
+    let (mut added, mut duplicates, mut conflicts) = (0, 0, 0);
+    msg::print_message(&"Writing the lookup tables... 0.0%".to_string(), 3);
+    for i in 0..bucket::BUCKETS {
+        let table = afdb_lookup::table_file(&dir, i);
+        let mut entries: HashMap<String, String> = HashMap::new();
+        if Path::new(&table).exists() {
+            for line in BufReader::new(File::open(&table)?).lines() {
+                let line = line?;
+                if let Some((hash, columns)) = line.split_once('\t') {
+                    entries.insert(hash.to_string(), columns.to_string());
+                }
+            }
+        }
+        for record in bucket::read_bucket(&bucket_dir, i)? {
+            match entries.get(&record[0]) {
+                Some(columns) => {
+                    duplicates += 1;
+                    if columns.split('\t').next() != Some(record[1].as_str()) { conflicts += 1; }
+                },
+                None => {
+                    let columns = if record[2].is_empty() { record[1].clone() } else { format!("{}\t{}", record[1], record[2]) };
+                    entries.insert(record[0].clone(), columns);
+                    added += 1;
+                },
+            }
+        }
+
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable();
+        let part = format!("{}.part", table);
+        let mut writer = BufWriter::new(File::create(&part)?);
+        for (hash, columns) in entries {
+            writeln!(writer, "{}\t{}", hash, columns)?;
+        }
+        writer.flush()?;
+        std::fs::rename(&part, &table)?;
+        msg::print_message(&format!("\rWriting the lookup tables... {:.1}%", (i as f64 + 1.0) / 2.56), 3);
+    }
+    msg::println_message(&"\rWriting the lookup tables... 100.0% Done".to_string(), 3);
+    std::fs::remove_dir_all(&bucket_dir)?;
+
+    msg::println_message(&format!("{} entries added to the lookup tables in {}", added, dir), 3);
+    if duplicates > 0 {
+        msg::println_message(&format!("{} entries were already in the tables and skipped", duplicates), 3);
+    }
+    if conflicts > 0 {
+        err::warning(err::WRN_GENERAL, Some(format!("{} skipped entries have a 3Di sequence different from the one in the tables", conflicts)));
+    }
+
+    // The binary index no longer matches the tables
+    let mut stale = false;
+    for i in 0..bucket::BUCKETS {
+        let index = lookup_index::index_file(&dir, i);
+        if Path::new(&index).exists() {
+            std::fs::remove_file(index)?;
+            stale = true;
+        }
+    }
+    if stale {
+        msg::println_message(&format!("The lookup index in {} was removed; rebuild it with 'unicore lookup-index {}'", dir, output), 3);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::envs::error_handler as err;
+use crate::envs::variables as var;
+use crate::seq::afdb_lookup;
+use crate::seq::bucket;
+use crate::seq::lookup_index::{self, LookupIndex};
+use crate::util::arg_parser::Args;
+use crate::util::message as msg;
+
+pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
+    // Retrieve arguments
+    let tables = args.lookupindex_tables.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("lookup-index - tables".to_string())); });
+    let threads = var::threads().max(1);
+
+    let dir = afdb_lookup::table_dir(&tables);
+    if !afdb_lookup::tables_complete(&dir) {
+        err::error(err::ERR_GENERAL, Some(format!("AFDB lookup tables not found or incomplete in {}", dir)));
+    }
+
+    // Index the tables in parallel; tables with a valid index are skipped
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let failure: Mutex<Option<String>> = Mutex::new(None);
+    msg::print_message(&"Indexing the lookup tables... 0.0%".to_string(), 3);
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= bucket::BUCKETS || failure.lock().unwrap().is_some() { break; }
+                let index = lookup_index::index_file(&dir, i);
+                if LookupIndex::open(&index).is_err() {
+                    if let Err(e) = lookup_index::build_index(&afdb_lookup::table_file(&dir, i), &index) {
+                        *failure.lock().unwrap() = Some(format!("Failed to index {}: {}", afdb_lookup::table_file(&dir, i), e));
+                        break;
+                    }
+                }
+                let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+                msg::print_message(&format!("\rIndexing the lookup tables... {:.1}%", done as f64 / 2.56), 3);
+            });
+        }
+    });
+    if let Some(e) = failure.into_inner()? {
+        msg::println_message(&String::new(), 3);
+        err::error(err::ERR_GENERAL, Some(e));
+    }
+    msg::println_message(&"\rIndexing the lookup tables... 100.0% Done".to_string(), 3);
+    msg::println_message(&format!("Lookup index written to {}; the TSV tables are no longer needed for createdb", dir), 3);
+
+    Ok(())
+}
@@ -5,4 +5,9 @@ pub mod search;
 pub mod profile;
 pub mod tree;
 pub mod genetree;
+pub mod removespecies;
+pub mod lookupindex;
+pub mod lookupbuild;
+pub mod cache;
+pub mod exportcore;
 pub mod config;
\ No newline at end of file
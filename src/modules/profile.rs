@@ -1,5 +1,6 @@
 use crate::envs::variables as var;
 use crate::envs::error_handler as err;
+use crate::seq::m8::{Filter, Format, HitReader};
 use crate::util::arg_parser::Args;
 use crate::util::checkpoint as chkpnt;
 use crate::util::message as msg;
@@ -10,8 +11,9 @@ use std::fs::File;
 use std::io::{self, BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
 
-fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, print_copiness: bool) -> io::Result<()> {
-    let mut gene_to_spe: HashMap<String, HashSet<String>> = HashMap::new();
+fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, print_copiness: bool, format: Option<Format>, filter: &Filter) -> Result<(), Box<dyn std::error::Error>> {
+    // Identical sequences share one entry, listed once per gene in the mapping file
+    let mut gene_to_spe: HashMap<String, Vec<String>> = HashMap::new();
     let mut species_set: HashSet<String> = HashSet::new();
 
     // Read the gene to species list
@@ -22,7 +24,7 @@ fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, pr
         let af_gene = parts[0].to_string();
         let spe = parts[1].to_string();
 
-        gene_to_spe.entry(af_gene).or_insert_with(HashSet::new).insert(spe.clone());
+        gene_to_spe.entry(af_gene).or_default().push(spe.clone());
         species_set.insert(spe);
     }
 
@@ -34,8 +36,7 @@ fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, pr
     if let Some(output) = output.as_mut() {
         writeln!(output, "Query\tMultipleCopyPercent\tSingleCopyPercent")?;
     }
-    let file = File::open(tsv_file)?;
-    let reader = BufReader::new(file);
+    let reader = HitReader::open(tsv_file, format)?;
     let mut curr_query: Option<String> = None;
     let mut spe_cnt: HashMap<String, i32> = HashMap::new();
     let mut gene2spe: HashMap<String, HashSet<String>> = HashMap::new();
@@ -47,10 +48,17 @@ fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, pr
     let (mut total_cnt, mut core_cnt) = (0, 0);
 
     msg::print_message(&"Profiling the taxonomic distribution of the genes...".to_string(), 3);
-    for line in reader.lines().filter_map(|l| l.ok()) {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        let query = parts[0].to_string();
-        let target = parts[1];
+    let (mut hit_cnt, mut filtered_cnt) = (0, 0);
+    for hit in reader {
+        let hit = hit?;
+        // Hits below the score, coverage or e-value thresholds don't count as members
+        hit_cnt += 1;
+        if !filter.accepts(&hit)? {
+            filtered_cnt += 1;
+            continue;
+        }
+        let query = hit.query;
+        let target = hit.target.as_str();
 
         if Some(&query) != curr_query.as_ref() {
             if let Some(q) = curr_query.take() {
@@ -104,6 +112,9 @@ fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, pr
     }
     
     msg::println_message(&" Done".to_string(), 3);
+    if !filter.is_empty() {
+        msg::println_message(&format!("{} of {} hits removed by the score, coverage and e-value filters", filtered_cnt, hit_cnt), 3);
+    }
     msg::println_message(&format!("{} structural core genes found from {} candidates", core_cnt, total_cnt), 3);
     
     // Check if there is any species that has less than 50% of the core genes
@@ -117,6 +128,11 @@ fn profile(tsv_file: &str, mapping: &str, output_dir: &str, threshold: usize, pr
     Ok(())
 }
 
+// Name of the core gene file of a cluster representative or reference gene
+pub fn gene_name(query: &str) -> &str {
+    query.split('-').nth(1).unwrap_or(query)
+}
+
 fn output_statistics_and_genes<W: Write>(output: &mut Option<W>, query: &str, spe_cnt: &HashMap<String, i32>, gene2spe: &HashMap<String, HashSet<String>>, species_count: usize, threshold: usize, output_dir: &str) -> io::Result<bool> {
     let single_copy = spe_cnt.values().filter(|&&count| count == 1).count();
     let multiple_copy = spe_cnt.len();
@@ -132,11 +148,12 @@ fn output_statistics_and_genes<W: Write>(output: &mut Option<W>, query: &str, sp
 
     // Write out the gene list if it is considered as core gene
     if single_copy * 100 >= threshold * species_count {
-        let output_path = Path::new(output_dir).join(format!("{}.txt", query.split('-').nth(1).unwrap_or(query)));
+        let output_path = Path::new(output_dir).join(format!("{}.txt", gene_name(query)));
         let mut output_file = BufWriter::new(File::create(output_path)?);
 
         for (spe, targets) in gene2spe {
-            if targets.len() == 1 {
+            // Skip the species with multiple copies, including the copies sharing the same sequence
+            if spe_cnt.get(spe) == Some(&1) {
                 let target = targets.iter().next().unwrap();
                 writeln!(output_file, "{}\t{}", target, spe)?;
             }
@@ -153,6 +170,11 @@ pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::
     let output = args.profile_output.clone().unwrap_or_else(|| { crate::envs::error_handler::error(crate::envs::error_handler::ERR_ARGPARSE, Some("profile - output".to_string())); });
     let threshold = args.profile_threshold.unwrap_or_else(|| { crate::envs::error_handler::error(crate::envs::error_handler::ERR_ARGPARSE, Some("profile - threshold".to_string())); });
     let print_copiness = args.profile_print_copiness.unwrap_or_else(|| { crate::envs::error_handler::error(crate::envs::error_handler::ERR_ARGPARSE, Some("profile - print_copiness".to_string())); });
+    let format_output = args.profile_format_output.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("profile - format_output".to_string())); });
+    let min_bits = args.profile_min_bits.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("profile - min_bits".to_string())); });
+    let max_evalue = args.profile_max_evalue.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("profile - max_evalue".to_string())); });
+    let min_coverage = args.profile_min_coverage.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("profile - min_coverage".to_string())); });
+    let min_tmscore = args.profile_min_tmscore.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("profile - min_tmscore".to_string())); });
 
     // If there is no output directory, make one
     if !Path::new(&output).exists() {
@@ -162,10 +184,22 @@ pub fn run(args: &Args, _: &var::BinaryPaths) -> Result<(), Box<dyn std::error::
     // Write the checkpoint file
     chkpnt::write_checkpoint(&format!("{}/profile.chk", output), "0")?;
 
-    let mapping = format!("{}.map", input_db);
-    profile(&input_tsv, &mapping, &output, threshold, print_copiness)?;
+    // Clusters or hits made before the database changed no longer match its genes
+    let input_fingerprint = Path::new(&input_tsv).with_extension("fingerprint").display().to_string();
+    if chkpnt::is_stale(&input_fingerprint, &input_db)? {
+        err::error(err::ERR_GENERAL, Some(format!("{} was made from an older version of {}; run cluster or search again", input_tsv, input_db)));
+    }
 
-    // Write the checkpoint file
+    let mapping = format!("{}.map", input_db);
+    let format = match format_output {
+        Some(format) => Some(Format::parse(&format)?),
+        None => None,
+    };
+    let filter = Filter { min_bits, max_evalue, min_coverage, min_tmscore };
+    profile(&input_tsv, &mapping, &output, threshold, print_copiness, format, &filter)?;
+
+    // Write the fingerprint of the profiled database and the checkpoint file
+    chkpnt::write_fingerprint(&format!("{}/profile.fingerprint", output), &input_db)?;
     chkpnt::write_checkpoint(&format!("{}/profile.chk", output), "1")?;
     
     Ok(())
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, MAIN_SEPARATOR as SEP};
+use std::process::Command as Cmd;
+
+use crate::envs::error_handler as err;
+use crate::envs::variables as var;
+use crate::modules::createdb::{commit_staged, finish_staged, remove_staged, STAGED};
+use crate::seq::foldseek_db::{self, open_by_name};
+use crate::util::arg_parser::Args;
+use crate::util::checkpoint as chkpnt;
+use crate::util::command as cmd;
+use crate::util::message as msg;
+
+pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
+    // Retrieve arguments
+    let db = args.removespecies_db.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("remove-species - db".to_string())); });
+    let species_args = args.removespecies_species.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("remove-species - species".to_string())); });
+    let foldseek_verbosity = (match var::verbosity() { 4 => 3, 3 => 2, _ => var::verbosity() }).to_string();
+
+    let parent = match Path::new(&db).parent().map(|p| p.to_string_lossy().into_owned()) {
+        Some(p) if !p.is_empty() => p,
+        _ => ".".to_string(),
+    };
+    let checkpoint_file = format!("{}{}createdb.chk", parent, SEP);
+    // Finish an append or removal interrupted while swapping its staged files into the database
+    let swap_marker = format!("{}{}swap.chk", parent, SEP);
+    if finish_staged(&db, &swap_marker, &checkpoint_file)?.as_deref() == Some("remove-species") {
+        msg::println_message(&"The species of the interrupted run are removed; run remove-species again to remove other species".to_string(), 3);
+        return Ok(());
+    }
+    if !Path::new(&checkpoint_file).exists() || chkpnt::read_checkpoint(&checkpoint_file)? != "1" {
+        err::error(err::ERR_GENERAL, Some(format!("No finished database found at {}", db)));
+    }
+    // Files of a run interrupted before the swap are left over; the database itself is intact
+    remove_staged(&db)?;
+
+    // Species are given directly or listed in files
+    let mut species = HashSet::new();
+    for arg in species_args {
+        if Path::new(&arg).is_file() {
+            for line in std::fs::read_to_string(&arg)?.lines() {
+                if !line.trim().is_empty() { species.insert(line.trim().to_string()); }
+            }
+        } else {
+            species.insert(arg);
+        }
+    }
+
+    // Split the mapping file into the rows to keep and to remove
+    let mapping_file = format!("{}.map", db);
+    let mut kept_rows = Vec::new();
+    let mut kept_names = HashSet::new();
+    let mut found = HashSet::new();
+    let mut removed_names = HashSet::new();
+    for line in BufReader::new(File::open(&mapping_file)?).lines() {
+        let line = line?;
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 3 { continue; }
+        if species.contains(cols[1]) {
+            found.insert(cols[1].to_string());
+            removed_names.insert(cols[0].to_string());
+        } else {
+            kept_names.insert(cols[0].to_string());
+            kept_rows.push(line);
+        }
+    }
+    let mut missing = species.difference(&found).cloned().collect::<Vec<_>>();
+    if !missing.is_empty() {
+        missing.sort();
+        err::error(err::ERR_GENERAL, Some(format!("Species not found in the database: {}", missing.join(", "))));
+    }
+    if kept_rows.is_empty() {
+        err::error(err::ERR_GENERAL, Some("All species would be removed from the database".to_string()));
+    }
+    // Sequences shared with the remaining species are kept
+    let removed_count = removed_names.difference(&kept_names).count();
+    msg::println_message(&format!("Removing {} species and {} sequences from {}", species.len(), removed_count, db), 3);
+
+    // Write the keys of the entries to keep
+    let reader = open_by_name(&db)?;
+    let key_file = format!("{}{}kept_keys.tsv", parent, SEP);
+    let mut key_writer = BufWriter::new(File::create(&key_file)?);
+    for key in kept_names.iter().filter_map(|name| reader.key_of(name)) {
+        writeln!(key_writer, "{}", key)?;
+    }
+    key_writer.flush()?;
+
+    // Create the subsets of the databases and stage them with the filtered tables; the database is replaced only at the end
+    let foldseek_path = match &bin.get("foldseek") {
+        Some(bin) => &bin.path,
+        _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
+    };
+    let staged_db = format!("{}{}", db, STAGED);
+    for suffix in ["", "_ss", "_h"] {
+        let (original, subset) = (format!("{}{}", db, suffix), format!("{}{}", staged_db, suffix));
+        cmd::run(Cmd::new(foldseek_path).arg("base:createsubdb").arg(&key_file).arg(&original).arg(&subset).arg("--subdb-mode").arg("0").arg("-v").arg(foldseek_verbosity.as_str()));
+    }
+    std::fs::remove_file(&key_file)?;
+    foldseek_db::write_lookup(&staged_db)?;
+
+    // Filter the mapping file, the species metadata and the provenance of the 3Di sequences
+    let mut mapping_writer = BufWriter::new(File::create(format!("{}.map", staged_db))?);
+    for row in kept_rows {
+        writeln!(mapping_writer, "{}", row)?;
+    }
+    mapping_writer.flush()?;
+    let meta_file = format!("{}.meta", db);
+    if Path::new(&meta_file).exists() {
+        let mut meta_writer = BufWriter::new(File::create(format!("{}.meta", staged_db))?);
+        for (i, line) in BufReader::new(File::open(&meta_file)?).lines().enumerate() {
+            let line = line?;
+            if i == 0 || !species.contains(line.split('\t').next().unwrap_or("")) {
+                writeln!(meta_writer, "{}", line)?;
+            }
+        }
+        meta_writer.flush()?;
+    }
+    let provenance_file = format!("{}.provenance.tsv", db);
+    if Path::new(&provenance_file).exists() {
+        let mut provenance_writer = BufWriter::new(File::create(format!("{}.provenance.tsv", staged_db))?);
+        for (i, line) in BufReader::new(File::open(&provenance_file)?).lines().enumerate() {
+            let line = line?;
+            if i == 0 || kept_names.contains(line.split('\t').next().unwrap_or("")) {
+                writeln!(provenance_writer, "{}", line)?;
+            }
+        }
+        provenance_writer.flush()?;
+    }
+
+    // Swap the staged files into the database
+    commit_staged(&db, "remove-species", &swap_marker, &checkpoint_file)?;
+
+    Ok(())
+}
@@ -1,10 +1,52 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
 use crate::util::arg_parser::Args;
 use crate::envs::error_handler as err;
+use crate::seq::{m8, orthology};
 use crate::util::command as cmd;
 use crate::util::checkpoint as chkpnt;
+use crate::util::message as msg;
 
-// Run foldseek search and convertalis
+// Species of each sequence entry in the mapping file of a database
+fn read_species(mapping: &str) -> Result<HashMap<String, Vec<String>>, Box<dyn std::error::Error>> {
+    let mut species: HashMap<String, Vec<String>> = HashMap::new();
+    for line in BufReader::new(File::open(mapping)?).lines() {
+        let line = line?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 2 { continue; }
+        species.entry(parts[0].to_string()).or_default().push(parts[1].to_string());
+    }
+    Ok(species)
+}
+
+// Keep the reciprocal best hits of the forward search, by the reverse search, per reference gene and species
+fn filter_orthologs(forward_m8: &str, reverse_m8: &str, mapping: &str, ratio: f64, format_output: &Option<String>, output_m8: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let format = || format_output.as_deref().map(m8::Format::parse).transpose();
+    let species = read_species(mapping)?;
+    let (forward, header) = m8::read_hits(forward_m8, format()?)?;
+    let (reverse, _) = m8::read_hits(reverse_m8, format()?)?;
+    for hit in forward.iter().chain(&reverse) {
+        if hit.score("bits")?.is_none() {
+            return Err("Orthology mode needs the bits column in the search result".into());
+        }
+    }
+    let keep = orthology::reciprocal_best_hits(&forward, &reverse, &species, ratio);
+    let mut writer = BufWriter::new(File::create(output_m8)?);
+    if let Some(header) = header {
+        writeln!(writer, "{}", header)?;
+    }
+    for (hit, _) in forward.iter().zip(&keep).filter(|(_, keep)| **keep) {
+        writeln!(writer, "{}", hit.line)?;
+    }
+    writer.flush()?;
+    msg::println_message(&format!("Kept {} of {} hits as reciprocal best hits", keep.iter().filter(|k| **k).count(), forward.len()), 3);
+    Ok(())
+}
+
+// Run foldseek or mmseqs search and convertalis
+// In orthology mode, the input is searched back against the target and only reciprocal best hits are kept
 pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(), Box<dyn std::error::Error>> {
     // Retrieve arguments
     let input = args.search_input.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - input".to_string())); });
@@ -13,6 +55,10 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     let tmp = args.search_tmp.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - tmp".to_string())); });
     let keep_aln_db = args.search_keep_aln_db.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - keep_aln_db".to_string())); });
     let search_options = args.search_search_options.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - foldseek_args".to_string())); });
+    let backend = args.search_backend.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - backend".to_string())); });
+    let orthology = args.search_orthology.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - orthology".to_string())); });
+    let inparalog_ratio = args.search_inparalog_ratio.unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - inparalog_ratio".to_string())); });
+    let format_output = args.search_format_output.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("search - format_output".to_string())); });
     let threads = crate::envs::variables::threads();
     let threads_str = threads.to_string();
 
@@ -33,14 +79,21 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     // foldseek_arg into vector, parsing by space
     let foldseek_args: Vec<&str> = search_options.split_whitespace().collect();
 
-    // Get foldseek
-    let foldseek_path = match &bin.get("foldseek") {
+    // Get foldseek, or mmseqs to search by the amino acid sequences of the same databases
+    let foldseek_path = match &bin.get(&backend) {
         Some(bin) => &bin.path,
-        _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some("foldseek".to_string())); }
+        _none => { err::error(err::ERR_BINARY_NOT_FOUND, Some(backend.clone())); }
     };
 
+    let mapping = format!("{}.map", input);
+    if orthology && !Path::new(&mapping).exists() {
+        err::error(err::ERR_GENERAL, Some(format!("Orthology mode needs the species of the input database in {}", mapping)));
+    }
+
     let output_aln_db = format!("{}_aln", output);
     let output_m8 = format!("{}.m8", output);
+    // In orthology mode, the unfiltered hits of both directions are kept next to the filtered ones
+    let forward_m8 = if orthology { format!("{}.fwd.m8", output) } else { output_m8.clone() };
     let mut foldseek_flag = vec![
         "search", "--threads", threads_str.as_str(), &target, &input, &output_aln_db, &tmp,
     ];
@@ -54,12 +107,39 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
 
     // Run foldseek convertalis
     let mut cmd = std::process::Command::new(foldseek_path);
-    let foldseek_flag = vec![
-        "convertalis", "--threads", threads_str.as_str(), &target, &input, &output_aln_db, &output_m8,
+    let mut foldseek_flag = vec![
+        "convertalis", "--threads", threads_str.as_str(), &target, &input, &output_aln_db, &forward_m8,
     ];
+    // Custom columns of the hit table
+    if let Some(format) = &format_output {
+        foldseek_flag.extend(["--format-output", format.as_str()]);
+    }
     let mut cmd = cmd.args(&foldseek_flag);
     cmd::run(&mut cmd);
 
+    if orthology {
+        // Search the input back against the target
+        let reverse_aln_db = format!("{}_aln_rev", output);
+        let reverse_m8 = format!("{}.rev.m8", output);
+        let mut foldseek_flag = vec![
+            "search", "--threads", threads_str.as_str(), &input, &target, &reverse_aln_db, &tmp,
+        ];
+        foldseek_flag.extend(foldseek_args.iter());
+        cmd::run(std::process::Command::new(foldseek_path).args(&foldseek_flag));
+        let mut foldseek_flag = vec![
+            "convertalis", "--threads", threads_str.as_str(), &input, &target, &reverse_aln_db, &reverse_m8,
+        ];
+        if let Some(format) = &format_output {
+            foldseek_flag.extend(["--format-output", format.as_str()]);
+        }
+        cmd::run(std::process::Command::new(foldseek_path).args(&foldseek_flag));
+        if !keep_aln_db {
+            cmd::run(std::process::Command::new(foldseek_path).args(["rmdb", &reverse_aln_db]));
+        }
+
+        filter_orthologs(&forward_m8, &reverse_m8, &mapping, inparalog_ratio, &format_output, &output_m8)?;
+    }
+
     // Delete intermediate database
     if !keep_aln_db {
         let mut cmd = std::process::Command::new(foldseek_path);
@@ -73,7 +153,8 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
 
     // TODO: implement detection and removal of foldseek search temporary results
 
-    // Write the checkpoint file
+    // Write the fingerprint of the searched database and the checkpoint file
+    chkpnt::write_fingerprint(&format!("{}.fingerprint", output), &input)?;
     chkpnt::write_checkpoint(&format!("{}/search.chk", parent), "1")?;
 
     Ok(())
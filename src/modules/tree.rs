@@ -35,6 +35,17 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     // Write the checkpoint file
     chkpnt::write_checkpoint(&format!("{}/tree.chk", output), "0")?;
 
+    // Core genes profiled before the database changed no longer match its genes
+    if chkpnt::is_stale(&format!("{}/profile.fingerprint", input), &db)? {
+        err::error(err::ERR_GENERAL, Some(format!("{} was profiled from an older version of {}; run cluster and profile again", input, db)));
+    }
+
+    // Carry the species metadata from the sample sheet over to the tree output
+    let metadata = format!("{}.meta", db);
+    if Path::new(&metadata).exists() {
+        fs::copy(&metadata, Path::new(&output).join("species_metadata.tsv"))?;
+    }
+
     // print out threads
     msg::println_message(&format!("Using {} threads", threads), 4);
 
@@ -157,7 +168,8 @@ pub fn run(args: &Args, bin: &crate::envs::variables::BinaryPaths) -> Result<(),
     } else { err::error(err::ERR_GENERAL, Some("Unrecognized tree builder".to_string())); }
     msg::println_message(&" Done".to_string(), 3);
 
-    // Write the checkpoint file
+    // Write the fingerprint of the database and the checkpoint file
+    chkpnt::write_fingerprint(&format!("{}/tree.fingerprint", output), &db)?;
     chkpnt::write_checkpoint(&format!("{}/tree.chk", output), "1")?;
 
     Ok(())
@@ -298,7 +310,7 @@ pub fn run_fasttree(fasttree_path: &String, output_dir: &String, msa_fasta: &Str
 // Only write columns that have >=threshold coverage
 fn filter_msa(input_msa: &String, output_msa: &String, threshold: usize) -> Result<(), Box<dyn std::error::Error>> {
     // Read in fasta file
-    let msa: HashMap<String, String> = fasta::read_fasta(input_msa);
+    let msa: HashMap<String, String> = fasta::read_fasta(input_msa)?;
     let seq_num = msa.len();
 
     // Iterate through the sequences and fill non_gap_cnt
@@ -1,18 +1,139 @@
-use crate::seq::fasta_io::write_fasta;
-use crate::seq::create_gene_specific_fasta::read_db;
+use crate::seq::bucket;
+use crate::seq::lookup_index::{self, LookupIndex, TableEntry};
+use crate::seq::foldseek_db::{header_name, DbReader, SeqDbWriter, DBTYPE_AMINO_ACIDS};
 use crate::util::message::print_message as mprint;
 use crate::util::message::println_message as mprintln;
-use crate::util::command as cmd;
 use crate::envs::error_handler as err;
 
 use std::collections::HashMap;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::path::MAIN_SEPARATOR as SEP;
-use std::process::Command as Cmd;
 
 use reqwest;
 
-fn download_table(path: &String) -> Result<(), Box<dyn std::error::Error>> {
+// Server of the lookup tables; can be replaced by a mirror with the option or the environment variable
+pub const DEFAULT_URL: &str = "https://unicore.steineggerlab.workers.dev";
+pub const URL_ENV: &str = "UNICORE_AFDB_URL";
+// Checksum manifest of the compressed tables in md5sum format
+const MANIFEST: &str = "md5sums.txt";
+const RETRIES: u32 = 5;
+// Environment variable for the download policy of the lookup tables
+pub const DOWNLOAD_ENV: &str = "UNICORE_LOOKUP_DOWNLOAD";
+// Environment variable to accept tables without a checksum manifest, e.g. from a mirror without one
+pub const UNVERIFIED_ENV: &str = "UNICORE_LOOKUP_UNVERIFIED";
+
+// What to do if the lookup tables are missing or incomplete
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DownloadPolicy {
+    // Ask for confirmation on the terminal; fails without a terminal
+    Ask,
+    // Download without confirmation
+    Always,
+    // Skip the lookup and predict all sequences with ProstT5
+    Never,
+    // Stop with an error
+    Fail,
+}
+
+impl DownloadPolicy {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(DownloadPolicy::Ask),
+            "always" => Ok(DownloadPolicy::Always),
+            "never" => Ok(DownloadPolicy::Never),
+            "fail" => Ok(DownloadPolicy::Fail),
+            _ => Err(format!("Download policy `{}` is not one of ask, always, never, fail", s)),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            DownloadPolicy::Ask => "ask",
+            DownloadPolicy::Always => "always",
+            DownloadPolicy::Never => "never",
+            DownloadPolicy::Fail => "fail",
+        }
+    }
+}
+
+// Download policy given by the option, the environment variable or the default, with where it came from
+pub fn download_policy(option: Option<DownloadPolicy>) -> Result<(DownloadPolicy, &'static str), String> {
+    if let Some(policy) = option {
+        return Ok((policy, "--lookup-download"));
+    }
+    match std::env::var(DOWNLOAD_ENV).ok().filter(|v| !v.is_empty()) {
+        Some(value) => Ok((DownloadPolicy::parse(&value)?, DOWNLOAD_ENV)),
+        None => Ok((DownloadPolicy::Ask, "default")),
+    }
+}
+
+// Base URL of the lookup tables, given by the option, the environment variable or the default
+pub fn base_url(option: Option<String>) -> String {
+    let url = option.or_else(|| std::env::var(URL_ENV).ok().filter(|u| !u.is_empty())).unwrap_or(DEFAULT_URL.to_string());
+    url.trim_end_matches('/').to_string()
+}
+
+// Directory of the lookup tables; either the given directory or its 'md5' subdirectory
+pub fn table_dir(path: &str) -> String {
+    let has_tables = |dir: &str| std::path::Path::new(&table_file(dir, 0)).exists() || std::path::Path::new(&lookup_index::index_file(dir, 0)).exists();
+    if has_tables(path) { path.to_string() } else { format!("{}{}md5", path, SEP) }
+}
+
+pub fn table_file(path: &str, index: usize) -> String {
+    format!("{}{}{:02x}.tsv", path, SEP, index)
+}
+
+// Tables are complete only if every one of them is downloaded and decompressed
+pub fn tables_complete(path: &str) -> bool {
+    (0..bucket::BUCKETS).all(|i| std::path::Path::new(&table_file(path, i)).exists())
+}
+
+// MD5 checksum of a file
+fn file_md5(file: &str) -> std::io::Result<String> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(file)?);
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 { break; }
+        context.consume(&buffer[..n]);
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
+// Parse a checksum manifest in md5sum format into a map from file names to checksums
+fn parse_manifest(content: &str) -> HashMap<String, String> {
+    content.lines().filter_map(|line| {
+        let mut split = line.split_whitespace();
+        let checksum = split.next()?.to_lowercase();
+        let name = split.next()?.trim_start_matches('*');
+        Some((name.rsplit('/').next().unwrap_or(name).to_string(), checksum))
+    }).collect()
+}
+
+// Download a file, resuming from the partially downloaded part if any
+fn download_file(client: &reqwest::blocking::Client, url: &str, part: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let offset = std::fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    let mut resp = request.send()?;
+    let status = resp.status();
+    // The part is already complete
+    if offset > 0 && status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE { return Ok(()); }
+    if !status.is_success() {
+        return Err(format!("{} returned {}", url, status).into());
+    }
+    // Servers without range support send the whole file again
+    let resume = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut file = std::fs::OpenOptions::new().create(true).write(true).append(resume).truncate(!resume).open(part)?;
+    std::io::copy(&mut resp, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn download_table(path: &str, base_url: &str) -> Result<(), Box<dyn std::error::Error>> {
     // create directory if not exists
     if std::path::Path::new(path).exists() {
         mprintln(&format!("Directory {} already exists.", path), 4);
@@ -20,98 +141,176 @@ fn download_table(path: &String) -> Result<(), Box<dyn std::error::Error>> {
         mprintln(&format!("Creating directory {}...", path), 4);
         std::fs::create_dir_all(path)?;
     }
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(30))
+        .timeout(None)
+        .build()?;
+
+    // checksums of the compressed tables
+    let manifest_url = format!("{}/md5/{}", base_url, MANIFEST);
+    let resp = client.get(&manifest_url).send()?;
+    let manifest = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        if !std::env::var(UNVERIFIED_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+            return Err(format!("No checksum manifest found at {}; set {}=1 to download the tables without verification", manifest_url, UNVERIFIED_ENV).into());
+        }
+        err::warning(err::WRN_GENERAL, Some(format!("No checksum manifest found at {}; downloaded tables are not verified as {} is set", manifest_url, UNVERIFIED_ENV)));
+        None
+    } else {
+        Some(parse_manifest(&resp.error_for_status()?.text()?))
+    };
 
-    // download the tables
+    // download and decompress the tables; finished tables are kept when resuming
     mprint(&"Downloading AFDB lookup tables (this may take a while)... 0.0%".to_string(), 3);
-    for i in 0..256 {
-        let hex = format!("{:02x}", i);
-        let url = format!("https://unicore.steineggerlab.workers.dev/md5/{}.tsv.gz", hex);
-        let file = format!("{}{}{}.tsv.gz", path, SEP, hex);
-        let mut resp = reqwest::blocking::get(&url)?;
-        let mut file = std::fs::File::create(&file)?;
-        std::io::copy(&mut resp, &mut file)?;
+    for i in 0..bucket::BUCKETS {
+        let table = table_file(path, i);
+        if std::path::Path::new(&table).exists() { continue; }
+        let name = format!("{:02x}.tsv.gz", i);
+        let url = format!("{}/md5/{}", base_url, name);
+        let gz = format!("{}.gz", table);
+        let part = format!("{}.part", gz);
+        let mut attempt = 0;
+        while !std::path::Path::new(&gz).exists() {
+            attempt += 1;
+            let result = download_file(&client, &url, &part).and_then(|_| {
+                let Some(manifest) = &manifest else { return Ok(()); };
+                let expected = manifest.get(&name).ok_or(format!("{} is not listed in the checksum manifest", name))?;
+                let actual = file_md5(&part)?;
+                if *expected != actual {
+                    // corrupt download; start over
+                    std::fs::remove_file(&part)?;
+                    return Err(format!("Checksum mismatch of {}: expected {}, got {}", name, expected, actual).into());
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => std::fs::rename(&part, &gz)?,
+                Err(e) if attempt < RETRIES => {
+                    err::warning(err::WRN_GENERAL, Some(format!("Failed to download {} ({}); retrying ({}/{})", url, e, attempt, RETRIES - 1)));
+                    std::thread::sleep(std::time::Duration::from_secs(1 << attempt.min(6)));
+                },
+                Err(e) => return Err(format!("Failed to download {} after {} attempts: {}", url, RETRIES, e).into()),
+            }
+        }
+
+        // decompress the table
+        let table_part = format!("{}.part", table);
+        let mut decoder = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(std::fs::File::open(&gz)?));
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&table_part)?);
+        std::io::copy(&mut decoder, &mut writer)?;
+        writer.flush()?;
+        std::fs::rename(&table_part, &table)?;
+        std::fs::remove_file(&gz)?;
         mprint(&format!("\rDownloading AFDB lookup tables (this may take a while)... {:.1}%", (i as f64 + 1.0) / 2.56), 3);
     }
     mprintln(&"\rDownloading AFDB lookup tables (this may take a while)... 100.0% Done".to_string(), 3);
 
-    // decompress the tables
-    mprint(&"Decompressing the tables... 0.0%".to_string(), 3);
-    for i in 0..256 {
-        let hex = format!("{:02x}", i);
-        let file = format!("{}{}{}.tsv.gz", path, SEP, hex);
-        cmd::run(Cmd::new("gzip").arg("-d").arg(&file));
-        mprint(&format!("\rDecompressing the tables... {:.1}%", (i as f64 + 1.0) / 2.56), 3);
-    }
-    mprintln(&"\rDecompressing the tables... 100.0% Done".to_string(), 3);
-
     Ok(())
 }
 
-pub fn run_afdb(fasta_data: &HashMap<String, String>, afdb_lookup: &String, converted_aa: &String, converted_ss: &String, combined_aa: &String) -> Result<(), Box<dyn std::error::Error>> {
-    // check if the directory is present
-    let path = afdb_lookup.clone();
-    let mut md5_path = format!("{}{}md5", path, SEP);
-    if std::path::Path::new(&path).exists() && std::fs::File::open(&format!("{}{}00.tsv", path, SEP)).is_ok() { md5_path = path.clone(); }
-    if std::fs::File::open(&format!("{}{}00.tsv", md5_path, SEP)).is_err() {
-        mprintln(&"AFDB lookup tables not found.".to_string(), 0);
-        mprint(&format!("Trying to download the tables to {} (~30GB). Continue? [y/n]: ", path), 0);
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        if input.trim().to_lowercase() != "y" {
-            mprintln(&"Download cancelled. Aborting the program.".to_string(), 0);
-            std::process::exit(0);
-        }
-        download_table(&md5_path)?;
-    }
+// Key of a sequence in the lookup tables; MD5 hash of the sequence with a line feed
+pub fn table_hash(seq: &str) -> String {
+    let mut bytes = seq.as_bytes().to_vec(); bytes.push(10);
+    format!("{:x}", md5::compute(bytes))
+}
 
-    let mut converted_aa_data: HashMap<String, String> = HashMap::new();
-    let mut converted_ss_data: HashMap<String, String> = HashMap::new();
-    let mut combined_data: HashMap<String, String> = HashMap::new();
+// Make sure the AFDB tables are available, downloading them if the policy allows
+// Returns false if the lookup is skipped and all sequences are to be predicted
+pub fn prepare_tables(afdb_lookup: &str, base_url: &str, policy: DownloadPolicy) -> Result<bool, Box<dyn std::error::Error>> {
+    let path = afdb_lookup.to_string();
+    let md5_path = table_dir(&path);
+    if lookup_index::index_complete(&md5_path) || tables_complete(&md5_path) {
+        return Ok(true);
+    }
+    let partial = (0..bucket::BUCKETS).any(|i| std::path::Path::new(&table_file(&md5_path, i)).exists());
+    let state = if partial { "incomplete" } else { "not found" };
+    match policy {
+        DownloadPolicy::Always => {
+            mprintln(&format!("AFDB lookup tables {}. Downloading the tables to {} from {}", state, path, base_url), 3);
+        },
+        DownloadPolicy::Never => {
+            err::warning(err::WRN_GENERAL, Some(format!("AFDB lookup tables {} in {}; the lookup is skipped and all sequences are predicted with ProstT5", state, path)));
+            return Ok(false);
+        },
+        DownloadPolicy::Fail => {
+            err::error(err::ERR_GENERAL, Some(format!("AFDB lookup tables {} in {}. Download them with --lookup-download always, or set {}", state, path, DOWNLOAD_ENV)));
+        },
+        DownloadPolicy::Ask => {
+            if !std::io::stdin().is_terminal() {
+                err::error(err::ERR_GENERAL, Some(format!("AFDB lookup tables {} in {}, and no terminal to confirm the download. Use --lookup-download always|never|fail or set {}", state, path, DOWNLOAD_ENV)));
+            }
+            mprintln(&format!("AFDB lookup tables {}.", state), 0);
+            if partial {
+                mprint(&format!("Trying to resume the download of the tables to {} from {}. Continue? [y/n]: ", path, base_url), 0);
+            } else {
+                mprint(&format!("Trying to download the tables to {} (~30GB) from {}. Continue? [y/n]: ", path, base_url), 0);
+            }
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            if input.trim().to_lowercase() != "y" {
+                err::error(err::ERR_GENERAL, Some("Download of the AFDB lookup tables cancelled".to_string()));
+            }
+        },
+    }
+    download_table(&md5_path, base_url)?;
+    Ok(true)
+}
 
-    let mut fasta_split = vec![HashMap::<String, (String, String)>::new(); 256];
-    // print(&"Splitting sequences by first two amino acids...".to_string(), 4);
-    for (h, seq) in fasta_data {
-        // add line feed to the end of the sequence
-        let mut bytes = seq.clone().into_bytes(); bytes.push(10);
-        let hash = format!("{:x}", md5::compute(bytes));
-        let idx = usize::from_str_radix(&hash[..2], 16)?;
-        fasta_split[idx].insert(h.clone(), (hash, seq.clone()));
+// Look up the sequences in the AFDB tables, which are made available by prepare_tables
+// Sequences are given in the buckets of bucket_dir split by the first byte of their table hash, as (name, sequence, hash) records
+// Found sequences are written to the converted database, and the rest to the combined fasta file for prediction
+// Hits with a mean pLDDT below min_plddt are predicted as well; hits without a pLDDT are always taken
+// The source of each found 3Di sequence is written to the provenance file
+// Returns the number of found and not found sequences
+pub fn run_afdb<W: Write, P: Write>(bucket_dir: &str, counts: &[usize], afdb_lookup: &str, min_plddt: Option<f32>, converted: &mut SeqDbWriter, combined_aa: &mut W, provenance: &mut P) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let path = afdb_lookup.to_string();
+    let md5_path = table_dir(&path);
+    let indexed = lookup_index::index_complete(&md5_path);
+    if !indexed {
+        mprintln(&format!("No lookup index found in {}; tables are loaded to memory. Build the index with 'unicore lookup-index {}' for faster lookups", md5_path, path), 3);
     }
 
-    let (mut conv, mut pred) = (0, 0);
+    let (mut conv, mut pred, mut low_confidence) = (0, 0, 0);
     mprint(&"Looking up the AFDB tables... 0.0%".to_string(), 3);
-    for i in 0..256 {
+    for (i, &count) in counts.iter().enumerate() {
         mprint(&format!("\rLooking up the AFDB tables... {:.1}%", (i as f64 + 1.0) / 2.56), 3);
         let hex = format!("{:02x}", i);
-        if fasta_split[i].is_empty() {
+        if count == 0 {
             mprintln(&format!("\nNo sequences starting with *{}. Skipping...", hex), 4);
             continue;
         }
-        let table = format!("{}{}{}.tsv", md5_path, SEP, hex);
-
-        // load table to memory
-        mprintln(&format!("\nLoading table for *{}...", hex), 4);
-        let mut table_map: HashMap<String, String> = HashMap::new();
-        let table_file = std::fs::File::open(table)?;
-        let table_reader = std::io::BufReader::new(table_file);
-        for line in table_reader.lines().filter_map(|l| l.ok()) {
-            let mut split = line.split('\t');
-            let key = split.next().unwrap().to_string();
-            let value = split.next().unwrap().to_string();
-            table_map.insert(key, value);
-        }
+        // open the binary index, or load table to memory
+        let index = if indexed { Some(LookupIndex::open(&lookup_index::index_file(&md5_path, i))?) } else { None };
+        let table_map: HashMap<String, TableEntry> = if indexed {
+            HashMap::new()
+        } else {
+            mprintln(&format!("\nLoading table for *{}...", hex), 4);
+            lookup_index::load_table(&table_file(&md5_path, i))?
+        };
 
         // convert sequences
         mprintln(&format!("Converting sequences starting with *{}...", hex), 4);
-        for (h, (hash, seq)) in &fasta_split[i] {
-            match table_map.get(hash) {
-                Some(converted_seq) => {
-                    converted_aa_data.insert(h.clone(), seq.clone());
-                    converted_ss_data.insert(h.clone(), converted_seq.clone());
+        for record in bucket::read_bucket(bucket_dir, i)? {
+            let (h, seq, hash) = (&record[0], &record[1], &record[2]);
+            let found = match &index {
+                Some(index) => index.get(hash),
+                None => table_map.get(hash).cloned(),
+            };
+            let found = match (found, min_plddt) {
+                (Some(entry), Some(min)) if entry.plddt.is_some_and(|p| p < min) => {
+                    low_confidence += 1;
+                    None
+                },
+                (found, _) => found,
+            };
+            match found {
+                Some(entry) => {
+                    converted.add(h, seq, &entry.ss)?;
+                    let plddt = entry.plddt.map(|p| p.to_string());
+                    writeln!(provenance, "{}\tafdb\t{}\t{}", h, entry.accession.as_deref().unwrap_or("-"), plddt.as_deref().unwrap_or("-"))?;
                     conv += 1;
                 },
                 None => {
-                    combined_data.insert(h.clone(), seq.clone());
+                    writeln!(combined_aa, ">{}\n{}", h, seq)?;
                     pred += 1;
                 },
             }
@@ -119,63 +318,42 @@ pub fn run_afdb(fasta_data: &HashMap<String, String>, afdb_lookup: &String, conv
     }
     mprintln(&"\rLooking up the AFDB tables... 100.0% Done".to_string(), 3);
     mprintln(&format!("{} sequences found from the lookup tables", conv), 3);
+    if low_confidence > 0 {
+        mprintln(&format!("{} hits below the pLDDT threshold are ignored", low_confidence), 3);
+    }
     mprintln(&format!("{} sequences not found and will be predicted", pred), 3);
 
-    write_fasta(&converted_aa, &converted_aa_data, true)?;
-    write_fasta(&converted_ss, &converted_ss_data, true)?;
-    write_fasta(&combined_aa, &combined_data, false)?;
-
-    Ok(())
+    Ok((conv, pred))
 }
 
-pub fn run_custom(fasta_data: &HashMap<String, String>, custom_lookup: &String, converted_aa: &String, converted_ss: &String, combined_aa: &String) -> Result<(), Box<dyn std::error::Error>> {
+// Load the custom lookup database as a map from amino acid sequences to 3Di sequences
+// Accessions are taken from the headers if the database has them
+pub fn load_custom(custom_lookup: &str) -> Result<HashMap<String, TableEntry>, Box<dyn std::error::Error>> {
     // check if the directory is present
-    let path = custom_lookup.clone();
+    let path = custom_lookup.to_string();
     let ss_path = format!("{}_ss", path);
-    if std::fs::File::open(&path).is_err() || std::fs::File::open(&ss_path).is_err() {
+    if std::fs::File::open(format!("{}.index", path)).is_err() || std::fs::File::open(format!("{}.index", ss_path)).is_err() {
         err::error(err::ERR_GENERAL, Some("Custom lookup database does not exist or improperly formatted.".to_string()));
     }
 
-    let mut converted_aa_data: HashMap<String, String> = HashMap::new();
-    let mut converted_ss_data: HashMap<String, String> = HashMap::new();
-    let mut combined_data: HashMap<String, String> = HashMap::new();
-
     // load table to memory
     mprintln(&"\nLoading the database...".to_string(), 3);
-    let table_aa = read_db(&path);
-    let table_ss = read_db(&ss_path);
-    if table_aa.len() != table_ss.len() {
-        err::error(err::ERR_GENERAL, Some("The custom lookup database is not properly formatted.".to_string()));
-    }
-    let mut table_map = HashMap::<String, String>::new();
-    for (aa, ss) in table_aa.into_iter().zip(table_ss.into_iter()) {
-        table_map.insert(aa, ss);
-    }
-
-    let cnt = fasta_data.len();
-    let (mut conv, mut pred) = (0, 0);
-    mprint(&"Looking up the custom database... 0.0%".to_string(), 3);
-    for (i, (h, seq)) in fasta_data.iter().enumerate() {
-        mprint(&format!("\rLooking up the custom database... {:.1}%", (i as f64 + 1.0) * 100.0 / (cnt as f64)), 3);
-        match table_map.get(seq) {
-            Some(converted_seq) => {
-                converted_aa_data.insert(h.clone(), seq.clone());
-                converted_ss_data.insert(h.clone(), converted_seq.clone());
-                conv += 1;
-            },
-            None => {
-                combined_data.insert(h.clone(), seq.clone());
-                pred += 1;
+    let table_aa = DbReader::open(&path)?;
+    let table_ss = DbReader::open(&ss_path)?;
+    if table_aa.dbtype() != DBTYPE_AMINO_ACIDS || table_ss.dbtype() != DBTYPE_AMINO_ACIDS {
+        err::error(err::ERR_GENERAL, Some("The custom lookup database is not a sequence database.".to_string()));
+    }
+    let table_h = DbReader::open(&format!("{}_h", path)).ok();
+    let mut table_map = HashMap::<String, TableEntry>::new();
+    for key in table_aa.keys() {
+        match (table_aa.get_str(key), table_ss.get_str(key)) {
+            (Some(aa), Some(ss)) => {
+                let accession = table_h.as_ref().and_then(|h| h.get_str(key)).map(|h| header_name(&h));
+                table_map.insert(aa, TableEntry { ss, accession, plddt: None });
             },
+            _ => err::error(err::ERR_GENERAL, Some("The custom lookup database is not properly formatted.".to_string())),
         }
     }
-    mprintln(&"\rLooking up the custom database... 100.0% Done".to_string(), 3);
-    mprintln(&format!("{} sequences found from the lookup database", conv), 3);
-    mprintln(&format!("{} sequences not found and will be predicted", pred), 3);
-
-    write_fasta(&converted_aa, &converted_aa_data, true)?;
-    write_fasta(&converted_ss, &converted_ss_data, true)?;
-    write_fasta(&combined_aa, &combined_data, false)?;
 
-    Ok(())
-}
\ No newline at end of file
+    Ok(table_map)
+}
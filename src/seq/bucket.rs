@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::MAIN_SEPARATOR as SEP;
+
+// Number of buckets; sequences are split by the first byte of their hash
+pub const BUCKETS: usize = 256;
+
+// Bucket index of a hexadecimal hash
+pub fn index(hash: &str) -> usize {
+    hash.get(..2).and_then(|h| usize::from_str_radix(h, 16).ok()).unwrap_or(0)
+}
+
+pub fn bucket_file(dir: &str, index: usize) -> String {
+    format!("{}{}{:02x}.tsv", dir, SEP, index)
+}
+
+// On-disk buckets of tab-separated records, so that each part of the input can be processed in memory
+pub struct Buckets {
+    writers: Vec<BufWriter<File>>,
+    counts: Vec<usize>,
+}
+
+impl Buckets {
+    pub fn create(dir: &str) -> io::Result<Buckets> {
+        if std::path::Path::new(dir).exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+        std::fs::create_dir_all(dir)?;
+        let mut writers = Vec::with_capacity(BUCKETS);
+        for i in 0..BUCKETS {
+            writers.push(BufWriter::new(File::create(bucket_file(dir, i))?));
+        }
+        Ok(Buckets { writers, counts: vec![0; BUCKETS] })
+    }
+
+    pub fn write(&mut self, index: usize, fields: &[&str]) -> io::Result<()> {
+        self.counts[index] += 1;
+        writeln!(self.writers[index], "{}", fields.join("\t"))
+    }
+
+    // Flush the buckets and return the number of records in each bucket
+    pub fn finish(self) -> io::Result<Vec<usize>> {
+        for mut writer in self.writers {
+            writer.flush()?;
+        }
+        Ok(self.counts)
+    }
+}
+
+// Read the records of a bucket
+pub fn read_bucket(dir: &str, index: usize) -> io::Result<Vec<Vec<String>>> {
+    let reader = BufReader::new(File::open(bucket_file(dir, index))?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        records.push(line?.split('\t').map(|f| f.to_string()).collect());
+    }
+    Ok(records)
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// Comparison of two clusterings of a database, by the representative and member names in their cluster tsv files
+
+// Clusters as (representative, members) in the order of the file
+pub type Clusters = Vec<(String, Vec<String>)>;
+
+// Read a cluster tsv file with a representative and a member on each line
+pub fn read_clusters(file: &str) -> io::Result<Clusters> {
+    parse_clusters(BufReader::new(File::open(file)?))
+}
+
+pub fn parse_clusters<R: BufRead>(reader: R) -> io::Result<Clusters> {
+    let mut clusters: Clusters = Vec::new();
+    let mut position: HashMap<String, usize> = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        let mut cols = line.split('\t');
+        let (Some(rep), Some(member)) = (cols.next(), cols.next()) else { continue; };
+        let i = *position.entry(rep.to_string()).or_insert_with(|| {
+            clusters.push((rep.to_string(), Vec::new()));
+            clusters.len() - 1
+        });
+        clusters[i].1.push(member.to_string());
+    }
+    Ok(clusters)
+}
+
+// Change of a cluster, named after its representative
+#[derive(Debug, PartialEq)]
+pub struct ClusterChange {
+    pub rep: String,
+    // new, removed, gained, lost, merged and split
+    pub changes: Vec<&'static str>,
+    pub before: usize,
+    pub after: usize,
+    pub gained: Vec<String>,
+    pub lost: Vec<String>,
+}
+
+// Compare the clusters before and after an update; unchanged clusters are left out
+// An old cluster is split if its members went to several new clusters; a new cluster is merged if it took
+// all remaining members of several old clusters, and so are the old clusters it took
+pub fn compare(old: &Clusters, new: &Clusters) -> Vec<ClusterChange> {
+    let new_rep: HashMap<&str, &str> = new.iter().flat_map(|(rep, members)| members.iter().map(move |m| (m.as_str(), rep.as_str()))).collect();
+    // New clusters of the members of each old cluster
+    let mut targets: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (rep, members) in old {
+        targets.insert(rep, members.iter().filter_map(|m| new_rep.get(m.as_str()).copied()).collect());
+    }
+    // Number of old clusters entirely taken by each new cluster
+    let mut absorbed: HashMap<&str, usize> = HashMap::new();
+    for target in targets.values().filter(|t| t.len() == 1) {
+        *absorbed.entry(target.iter().next().unwrap()).or_insert(0) += 1;
+    }
+    let is_merged = |rep: &str| absorbed.get(rep).is_some_and(|n| *n > 1);
+    let old_members: HashMap<&str, &Vec<String>> = old.iter().map(|(rep, members)| (rep.as_str(), members)).collect();
+    let new_members: HashMap<&str, &Vec<String>> = new.iter().map(|(rep, members)| (rep.as_str(), members)).collect();
+
+    let (empty, no_target) = (Vec::new(), HashSet::new());
+    let mut result = Vec::new();
+    let reps = new.iter().map(|(rep, _)| rep).chain(old.iter().map(|(rep, _)| rep).filter(|rep| !new_members.contains_key(rep.as_str())));
+    for rep in reps {
+        let before = old_members.get(rep.as_str()).copied().unwrap_or(&empty);
+        let after = new_members.get(rep.as_str()).copied().unwrap_or(&empty);
+        let (before_set, after_set): (HashSet<&String>, HashSet<&String>) = (before.iter().collect(), after.iter().collect());
+        let gained: Vec<String> = after.iter().filter(|m| !before_set.contains(m)).cloned().collect();
+        let lost: Vec<String> = before.iter().filter(|m| !after_set.contains(m)).cloned().collect();
+        let target = targets.get(rep.as_str()).unwrap_or(&no_target);
+
+        let mut changes = Vec::new();
+        if before.is_empty() { changes.push("new"); }
+        if after.is_empty() { changes.push("removed"); }
+        if !before.is_empty() && !gained.is_empty() { changes.push("gained"); }
+        if !after.is_empty() && !lost.is_empty() { changes.push("lost"); }
+        if is_merged(rep) || (target.len() == 1 && target.iter().all(|t| *t != rep && is_merged(t))) { changes.push("merged"); }
+        if target.len() > 1 { changes.push("split"); }
+        if changes.is_empty() { continue; }
+        result.push(ClusterChange { rep: rep.clone(), changes, before: before.len(), after: after.len(), gained, lost });
+    }
+    result
+}
+
+// Write the changes as a tsv file with a header
+pub fn write_changes(file: &str, changes: &[ClusterChange]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    writeln!(writer, "cluster\tchanges\tmembers_before\tmembers_after\tgained\tlost")?;
+    let list = |names: &[String]| if names.is_empty() { "-".to_string() } else { names.join(",") };
+    for change in changes {
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}", change.rep, change.changes.join(","), change.before, change.after, list(&change.gained), list(&change.lost))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let old = parse_clusters("a\ta\na\tb\nc\tc\nc\td\ne\te\ne\tf\ng\tg\n".as_bytes()).unwrap();
+        let new = parse_clusters("a\ta\na\tb\na\tx\nc\tc\nc\td\nc\te\ne\tf\ng\tg\nh\th\n".as_bytes()).unwrap();
+        let changes = compare(&old, &new);
+        let summary: Vec<(&str, Vec<&str>)> = changes.iter().map(|c| (c.rep.as_str(), c.changes.clone())).collect();
+        // a gained a new sequence; e was split into c and e; g is unchanged
+        assert_eq!(summary, vec![
+            ("a", vec!["gained"]),
+            ("c", vec!["gained"]),
+            ("e", vec!["lost", "split"]),
+            ("h", vec!["new"]),
+        ]);
+        assert_eq!(changes[2].lost, vec!["e".to_string()]);
+
+        // Old clusters c and e merged into c
+        let new = parse_clusters("a\ta\na\tb\nc\tc\nc\td\nc\te\nc\tf\ng\tg\n".as_bytes()).unwrap();
+        let summary: Vec<(String, Vec<&str>)> = compare(&old, &new).into_iter().map(|c| (c.rep, c.changes)).collect();
+        assert_eq!(summary, vec![("c".to_string(), vec!["gained", "merged"]), ("e".to_string(), vec!["removed", "merged"])]);
+    }
+}
@@ -4,43 +4,27 @@ use std::io::{BufWriter, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 
 use crate::envs::error_handler as err;
-
-// Read in one file
-pub fn read_db(filename: &String) -> Vec<String> {
-    let mut db: Vec<String> = Vec::new();
-    // Open the file
-    let reader = BufReader::new(File::open(filename).unwrap());
-    // If the first character's ascii value is 0, erase it
-    for line in reader.lines() {
-        let mut line = line.unwrap_or_else(|_| "Unable to read db".to_string());
-        if line.chars().next().unwrap() as u8 == 0 {
-            line.remove(0);
-        }
-        // Push if the length is greater than 0
-        if line.len() > 0 {
-            db.push(line);
-        }
-    }
-    db
-}
+use crate::seq::foldseek_db::{header_name, DbReader};
 
 pub fn create_gene_specific_fasta(input_db: &str, gene_dir: &PathBuf, gene_list: &Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
 
     // Read names, amino acid and 3di sequences
-    let names = read_db(&format!("{}_h", input_db));
-    let aa_seqs = read_db(&input_db.to_string());
-    let di_seqs = read_db(&format!("{}_ss", input_db));
+    let names = DbReader::open(&format!("{}_h", input_db))?;
+    let aa_seqs = DbReader::open(input_db)?;
+    let di_seqs = DbReader::open(&format!("{}_ss", input_db))?;
 
-    // Check the lengths are all same
-    if names.len() != aa_seqs.len() || names.len() != di_seqs.len() {
-        err::error(err::ERR_GENERAL, Some("Lengths of names, amino acid and 3di sequences in database are not same".to_string()));
-    }
-    // Create a hash map of names and sequences
+    // Create a hash map of names and sequences, joined by the keys of the databases
     let mut aa_hash = HashMap::new();
     let mut di_hash = HashMap::new();
-    for i in 0..names.len() {
-        aa_hash.insert(names[i].clone(), aa_seqs[i].clone());
-        di_hash.insert(names[i].clone(), di_seqs[i].clone());
+    for key in names.keys() {
+        let name = header_name(&names.get_str(key).unwrap_or_default());
+        match (aa_seqs.get_str(key), di_seqs.get_str(key)) {
+            (Some(aa_seq), Some(di_seq)) => {
+                aa_hash.insert(name.clone(), aa_seq);
+                di_hash.insert(name, di_seq);
+            },
+            _ => err::error(err::ERR_GENERAL, Some(format!("Entry {} of the database has no amino acid or 3di sequence", name))),
+        }
     }
 
     // Process each gene
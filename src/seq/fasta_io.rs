@@ -1,48 +1,101 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
 
-// Function that reads in a fasta file and outputs a hashmap of the sequences
-pub fn read_fasta(file: &str) -> HashMap<String, String> {
-    let file = File::open(file).expect("Unable to open file");
-    let reader = BufReader::new(file);
-    let mut sequences = HashMap::new();
-    let mut header = String::new();
-    let mut sequence = String::new();
-    for line in reader.lines().filter_map(|l| l.ok()) {
-        if line.starts_with('>') {
-            if !header.is_empty() {
-                sequences.insert(header.clone(), sequence.clone());
-                sequence.clear();
-            }
-            header = line[1..].to_string();
-        } else {
-            sequence.push_str(&line);
-        }
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+// Recognized extensions of fasta files and their compressed forms
+pub const FASTA_EXTENSIONS: [&str; 4] = ["fasta", "fa", "faa", "fas"];
+pub const COMPRESSION_EXTENSIONS: [&str; 3] = ["gz", "bz2", "xz"];
+
+// Function that splits the compression extension from the file name, if any
+fn strip_compression(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if COMPRESSION_EXTENSIONS.contains(&ext.to_lowercase().as_str()) => stem,
+        _ => name,
     }
-    sequences.insert(header, sequence);
-    sequences
 }
 
-pub fn write_fasta(file: &str, mapping: &HashMap<String, String>, sort: bool) -> io::Result<()> {
-    // Open the file
-    let file = File::create(file)?;
-    let mut file_writer = io::BufWriter::new(file);
+// Function that returns the format extension of the file, ignoring the compression extension
+pub fn format_extension(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    strip_compression(name).rsplit_once('.').map(|(_, ext)| ext.to_lowercase())
+}
 
-    // Sort the headers if requested
-    let mut headers: Vec<&String> = mapping.keys().collect();
-    if sort {
-        headers.sort();
+// Function that checks if the file is a (possibly compressed) fasta file
+pub fn is_fasta(path: &Path) -> bool {
+    format_extension(path).is_some_and(|ext| FASTA_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// Function that derives the species name from the file name
+// All compression and format suffixes are stripped, e.g. Species.faa.gz -> Species
+pub fn species_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = strip_compression(&name);
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => name.to_string(),
     }
+}
+
+// Function that opens a file for reading, transparently decompressing gzip, bzip2 and xz
+// Compression is detected from the magic bytes rather than the extension
+pub fn open_reader(file: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(file)?);
+    let magic = reader.fill_buf()?.to_vec();
+    let reader: Box<dyn BufRead> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(b"BZh") {
+        Box::new(BufReader::new(MultiBzDecoder::new(reader)))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader)))
+    } else {
+        Box::new(reader)
+    };
+    Ok(reader)
+}
 
-    // Write the sequences to the file
-    for header in headers {
-        writeln!(file_writer, ">{}\n{}", header, mapping[header])?;
+// Iterator over the records of a fasta file as (header, sequence), reading one record at a time
+pub struct FastaRecords {
+    reader: Box<dyn BufRead>,
+    header: Option<String>,
+    sequence: String,
+    line: String,
+}
+
+impl Iterator for FastaRecords {
+    type Item = io::Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Err(e) => return Some(Err(e)),
+                Ok(0) => return self.header.take().map(|h| Ok((h, std::mem::take(&mut self.sequence)))),
+                Ok(_) => {
+                    let line = self.line.trim_end_matches(['\n', '\r']);
+                    if let Some(h) = line.strip_prefix('>') {
+                        if let Some(prev) = self.header.replace(h.to_string()) {
+                            return Some(Ok((prev, std::mem::take(&mut self.sequence))));
+                        }
+                    } else if self.header.is_some() {
+                        self.sequence.push_str(line);
+                    }
+                },
+            }
+        }
     }
-    // for (header, sequence) in mapping {
-    //     writeln!(file_writer, ">{}\n{}", header, sequence)?;
-    // }
-    file_writer.flush()?;
+}
+
+pub fn fasta_records(file: &str) -> io::Result<FastaRecords> {
+    Ok(FastaRecords { reader: open_reader(file)?, header: None, sequence: String::new(), line: String::new() })
+}
 
-    Ok(())
-}
\ No newline at end of file
+// Function that reads in a fasta file and outputs a hashmap of the sequences
+// Fails on read errors, e.g. a truncated or corrupt compressed file, instead of returning a partial map
+pub fn read_fasta(file: &str) -> io::Result<HashMap<String, String>> {
+    fasta_records(file)?.collect()
+}
@@ -0,0 +1,250 @@
+use std::io::BufRead;
+
+use crate::envs::error_handler as err;
+use crate::seq::fasta_io;
+use crate::seq::translate;
+use crate::util::message as msg;
+
+// Segment of a feature location, 1-based inclusive
+struct Segment {
+    start: usize,
+    end: usize,
+    reverse: bool,
+    // '<' or '>' marks on the lower and upper ends
+    partial_lo: bool,
+    partial_hi: bool,
+}
+
+struct Feature {
+    location: String,
+    qualifiers: Vec<(String, String)>,
+}
+
+impl Feature {
+    fn qualifier(&self, key: &str) -> Option<&str> {
+        self.qualifiers.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+// Split the arguments of join(...) or order(...) on the top-level commas
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut last) = (0, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => { parts.push(&s[last..i]); last = i + 1; },
+            _ => {},
+        }
+    }
+    parts.push(&s[last..]);
+    parts
+}
+
+// Parse INSDC feature location into segments ordered in the direction of transcription
+// Returns None for locations that cannot be extracted from the record (e.g. remote references)
+fn parse_location(location: &str) -> Option<Vec<Segment>> {
+    let location = location.trim();
+    if let Some(inner) = location.strip_prefix("complement(").and_then(|l| l.strip_suffix(')')) {
+        let mut segments = parse_location(inner)?;
+        segments.reverse();
+        segments.iter_mut().for_each(|s| s.reverse = !s.reverse);
+        return Some(segments);
+    }
+    if let Some(inner) = location.strip_prefix("join(").or(location.strip_prefix("order(")).and_then(|l| l.strip_suffix(')')) {
+        let mut segments = Vec::new();
+        for part in split_top_level(inner) {
+            segments.extend(parse_location(part)?);
+        }
+        return Some(segments);
+    }
+    if location.contains(':') || location.contains('^') { return None; }
+    let (lo, hi) = location.split_once("..").unwrap_or((location, location));
+    let start = lo.trim_start_matches('<').parse().ok()?;
+    let end = hi.trim_start_matches('>').parse().ok()?;
+    Some(vec![Segment { start, end, reverse: false, partial_lo: lo.starts_with('<'), partial_hi: hi.starts_with('>') }])
+}
+
+// Obtain the protein sequence of a CDS, from /translation or by translating the CDS
+fn cds_protein(feature: &Feature, sequence: &str, genetic_code: u8) -> Result<Option<String>, String> {
+    if let Some(translation) = feature.qualifier("translation") {
+        return Ok(Some(translation.chars().filter(|c| !c.is_whitespace()).collect()));
+    }
+    if feature.qualifier("pseudo").is_some() || feature.qualifier("pseudogene").is_some() { return Ok(None); }
+    let Some(segments) = parse_location(&feature.location) else { return Ok(None); };
+    if segments.is_empty() || segments.iter().any(|s| s.start == 0 || s.start > s.end || s.end > sequence.len()) {
+        return Err(format!("location {} is out of the bounds of the sequence", feature.location));
+    }
+
+    let mut nucl = String::new();
+    for s in &segments {
+        let part = &sequence[s.start - 1..s.end];
+        if s.reverse { nucl.push_str(&translate::reverse_complement(part)); } else { nucl.push_str(part); }
+    }
+    let first = &segments[0];
+    let partial_5p = if first.reverse { first.partial_hi } else { first.partial_lo };
+    let offset = feature.qualifier("codon_start").and_then(|c| c.parse::<usize>().ok()).unwrap_or(1).saturating_sub(1).min(nucl.len());
+    let table = feature.qualifier("transl_table").and_then(|t| t.parse().ok()).unwrap_or(genetic_code);
+    match translate::translate(&nucl[offset..], table, offset == 0 && !partial_5p) {
+        Some(protein) => Ok(Some(protein)),
+        None => Err(format!("unsupported genetic code {}", table)),
+    }
+}
+
+// Read protein sequences of CDS features from GenBank or EMBL flat file
+// Gene names are taken from /locus_tag or /protein_id qualifiers
+// Returns the list of (gene name, protein sequence)
+pub fn read_flatfile_proteins(file: &str, genetic_code: u8) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let reader = fasta_io::open_reader(file)?;
+    let mut proteins = Vec::new();
+    let mut locus = String::new();
+    let mut features: Vec<Feature> = Vec::new();
+    let mut in_cds = false;
+    let mut sequence = String::new();
+    let (mut in_features, mut in_sequence) = (false, false);
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with("//") {
+            // End of the record
+            for (i, feature) in features.iter().enumerate() {
+                let name = feature.qualifier("locus_tag")
+                    .or(feature.qualifier("protein_id"))
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| format!("{}_cds{}", locus, i + 1));
+                match cds_protein(feature, &sequence, genetic_code) {
+                    Ok(Some(protein)) => proteins.push((name, protein)),
+                    Ok(None) => msg::println_message(&format!("Skipping CDS {} in {} as it has no translation", name, file), 4),
+                    Err(e) => err::warning(err::WRN_GENERAL, Some(format!("CDS {} in {}: {}; skipping", name, file, e))),
+                }
+            }
+            features.clear();
+            sequence.clear();
+            (in_features, in_sequence, in_cds) = (false, false, false);
+            continue;
+        }
+
+        // EMBL lines start with a two-letter code; the feature table is aligned with GenBank when the code is blanked
+        let body = if let Some(rest) = line.strip_prefix("FT") {
+            in_features = true;
+            format!("  {}", rest)
+        } else if line.starts_with("ID ") || line.starts_with("LOCUS") {
+            locus = line.split_whitespace().nth(1).unwrap_or("").trim_end_matches(';').to_string();
+            continue;
+        } else if line.starts_with("FEATURES") {
+            in_features = true;
+            continue;
+        } else if line.starts_with("ORIGIN") || line.starts_with("SQ ") {
+            (in_features, in_sequence) = (false, true);
+            continue;
+        } else if in_sequence {
+            sequence.extend(line.chars().filter(|c| c.is_ascii_alphabetic()));
+            continue;
+        } else if in_features && line.starts_with("     ") {
+            line
+        } else {
+            in_features = false;
+            continue;
+        };
+        if !in_features { continue; }
+
+        let key = body.get(5..21).unwrap_or("").trim();
+        if !key.is_empty() {
+            // New feature
+            in_cds = key == "CDS";
+            if in_cds {
+                features.push(Feature { location: body.get(21..).unwrap_or("").trim().to_string(), qualifiers: Vec::new() });
+            }
+            continue;
+        }
+        if !in_cds { continue; }
+        let feature = features.last_mut().unwrap();
+        let content = body.trim();
+        if let Some(qualifier) = content.strip_prefix('/') {
+            let (k, v) = qualifier.split_once('=').unwrap_or((qualifier, ""));
+            feature.qualifiers.push((k.to_string(), v.trim_matches('"').to_string()));
+        } else if let Some((_, value)) = feature.qualifiers.last_mut() {
+            // Continuation of the qualifier value
+            if !value.is_empty() { value.push(' '); }
+            value.push_str(content.trim_end_matches('"'));
+        } else {
+            // Continuation of the location
+            feature.location.push_str(content);
+        }
+    }
+
+    Ok(proteins)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1-6 ATGAAA, 10-15 TGGTAA, 20-25 CCTTAT, 30-35 GCCATG, 40-48 GTGAAATAA
+    const ORIGIN: &str = "atgaaaccct ggtaacccct cttatccccg ccatgccccg tgaaataacc cccccccccc";
+
+    fn read_record(name: &str, record: &str) -> Vec<(String, String)> {
+        let file = std::env::temp_dir().join(format!("unicore_flatfile_io_{}_{}", std::process::id(), name));
+        std::fs::write(&file, record).unwrap();
+        let proteins = read_flatfile_proteins(&file.to_string_lossy(), 1).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        proteins
+    }
+
+    #[test]
+    fn test_read_genbank_proteins() {
+        let record = format!("LOCUS       TEST01                    60 bp    DNA     linear   BCT 01-JAN-2000\n\
+            FEATURES             Location/Qualifiers\n     \
+            source          1..60\n                     \
+            /organism=\"Test organism\"\n     \
+            CDS             join(1..6,10..15)\n                     \
+            /locus_tag=\"T1_0001\"\n     \
+            CDS             complement(join(20..25,\n                     \
+            30..35))\n                     \
+            /locus_tag=\"T1_0002\"\n                     \
+            /codon_start=2\n     \
+            CDS             40..48\n                     \
+            /locus_tag=\"T1_0003\"\n                     \
+            /transl_table=11\n     \
+            CDS             40..48\n                     \
+            /protein_id=\"T1_0004.1\"\n                     \
+            /translation=\"MKVLAGGTSW\n                     \
+            AAGG\"\n\
+            ORIGIN\n        1 {}\n//\n", ORIGIN);
+        assert_eq!(read_record("test.gbk", &record), vec![
+            ("T1_0001".to_string(), "MKW".to_string()),
+            ("T1_0002".to_string(), "MA".to_string()),
+            ("T1_0003".to_string(), "MK".to_string()),
+            ("T1_0004.1".to_string(), "MKVLAGGTSWAAGG".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_read_embl_proteins() {
+        // Without transl_table the GTG start of TEST02_cds3 is read in the standard genetic code
+        let record = format!("ID   TEST02; SV 1; linear; genomic DNA; STD; PRO; 60 BP.\n\
+            XX\n\
+            FH   Key             Location/Qualifiers\n\
+            FT   source          1..60\n\
+            FT                   /organism=\"Test organism\"\n\
+            FT   CDS             complement(join(20..25,30..35))\n\
+            FT                   /locus_tag=\"T2_0001\"\n\
+            FT                   /codon_start=2\n\
+            FT                   /transl_table=11\n\
+            FT   CDS             join(1..6,\n\
+            FT                   10..15)\n\
+            FT                   /protein_id=\"T2_0002.1\"\n\
+            FT                   /translation=\"MKVLAGGTSW\n\
+            FT                   AAGG\"\n\
+            FT   CDS             40..48\n\
+            XX\n\
+            SQ   Sequence 60 BP;\n     {} 60\n//\n", ORIGIN);
+        assert_eq!(read_record("test.embl", &record), vec![
+            ("T2_0001".to_string(), "MA".to_string()),
+            ("T2_0002.1".to_string(), "MKVLAGGTSWAAGG".to_string()),
+            ("TEST02_cds3".to_string(), "VK".to_string()),
+        ]);
+    }
+}
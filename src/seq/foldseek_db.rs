@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+// Database types written in .dbtype files
+pub const DBTYPE_AMINO_ACIDS: i32 = 0;
+pub const DBTYPE_GENERIC_DB: i32 = 12;
+
+// Reader of a Foldseek/MMseqs2 database with random access by key
+// Entries are located by the .index file; split databases (.0, .1, ...) are read as one data file
+pub struct DbReader {
+    // Data files with the offset of their first byte
+    data: Vec<(usize, Mmap)>,
+    // (key, offset, length) sorted by key; the length includes the terminating null byte
+    index: Vec<(u32, usize, usize)>,
+    dbtype: i32,
+    // Keys of the entries by their names in the .lookup file
+    lookup: Option<HashMap<String, u32>>,
+}
+
+impl DbReader {
+    pub fn open(path: &str) -> Result<DbReader, Box<dyn std::error::Error>> {
+        // Data files; a single file or the parts of a split database
+        let mut files = Vec::new();
+        if Path::new(path).is_file() {
+            files.push(path.to_string());
+        } else {
+            while Path::new(&format!("{}.{}", path, files.len())).is_file() {
+                files.push(format!("{}.{}", path, files.len()));
+            }
+        }
+        if files.is_empty() {
+            return Err(format!("Database {} does not exist", path).into());
+        }
+        let mut data = Vec::new();
+        let mut start = 0;
+        for file in files {
+            let file = File::open(file)?;
+            let size = file.metadata()?.len() as usize;
+            // Empty files can't be mapped, and have no entries anyway
+            if size > 0 {
+                data.push((start, unsafe { Mmap::map(&file)? }));
+            }
+            start += size;
+        }
+
+        let mut index = Vec::new();
+        for line in BufReader::new(File::open(format!("{}.index", path))?).lines() {
+            let line = line?;
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 3 {
+                return Err(format!("Invalid line in {}.index: {}", path, line).into());
+            }
+            index.push((cols[0].parse()?, cols[1].parse()?, cols[2].parse()?));
+        }
+        index.sort_unstable();
+
+        let dbtype = match std::fs::read(format!("{}.dbtype", path)) {
+            Ok(bytes) if bytes.len() >= 4 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            Ok(_) => return Err(format!("Invalid dbtype file of {}", path).into()),
+            Err(_) => DBTYPE_GENERIC_DB,
+        };
+        // Compressed databases store the entries in zstd frames; flagged in the extended dbtype bits
+        if (dbtype >> 16) & 1 != 0 {
+            return Err(format!("Compressed database {} is not supported", path).into());
+        }
+
+        // A lookup file not matching the index, e.g. one left over from merging databases, is ignored
+        let lookup = match File::open(format!("{}.lookup", path)) {
+            Ok(file) => {
+                let mut lookup = HashMap::new();
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    let cols: Vec<&str> = line.split('\t').collect();
+                    if cols.len() < 2 {
+                        return Err(format!("Invalid line in {}.lookup: {}", path, line).into());
+                    }
+                    lookup.insert(cols[1].to_string(), cols[0].parse()?);
+                }
+                let matches = lookup.len() == index.len()
+                    && lookup.values().all(|key| index.binary_search_by_key(key, |(k, _, _)| *k).is_ok());
+                if matches { Some(lookup) } else { None }
+            },
+            Err(_) => None,
+        };
+
+        Ok(DbReader { data, index, dbtype, lookup })
+    }
+
+    // Basic database type without the extended bits
+    pub fn dbtype(&self) -> i32 {
+        self.dbtype & 0xffff
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    // Whether the entries can be found by their names
+    pub fn has_lookup(&self) -> bool {
+        self.lookup.is_some()
+    }
+
+    // Key of the entry with the name in the .lookup file
+    pub fn key_of(&self, name: &str) -> Option<u32> {
+        self.lookup.as_ref()?.get(name).copied()
+    }
+
+    // Keys of the entries in ascending order
+    pub fn keys(&self) -> impl Iterator<Item = u32> + '_ {
+        self.index.iter().map(|(key, _, _)| *key)
+    }
+
+    // Raw content of the entry without the terminating null byte
+    pub fn get(&self, key: u32) -> Option<&[u8]> {
+        let i = self.index.binary_search_by_key(&key, |(k, _, _)| *k).ok()?;
+        let (_, offset, length) = self.index[i];
+        let (start, data) = self.data.iter().rev().find(|(start, _)| *start <= offset)?;
+        let entry = data.get(offset - start..offset - start + length)?;
+        Some(entry.strip_suffix(&[0]).unwrap_or(entry))
+    }
+
+    // Content of the entry as text, without the trailing line feed
+    pub fn get_str(&self, key: u32) -> Option<String> {
+        let entry = self.get(key)?;
+        let entry = entry.strip_suffix(b"\n").unwrap_or(entry);
+        Some(String::from_utf8_lossy(entry).into_owned())
+    }
+}
+
+// Name of the sequence in a header entry; the first word of the header
+pub fn header_name(header: &str) -> String {
+    header.split_whitespace().next().unwrap_or("").to_string()
+}
+
+// Write the .lookup files of a sequence database and its _ss and _h databases from the names in the header database
+pub fn write_lookup(db: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let headers = DbReader::open(&format!("{}_h", db))?;
+    let lookup_file = format!("{}.lookup", db);
+    let mut writer = BufWriter::new(File::create(&lookup_file)?);
+    for key in headers.keys() {
+        writeln!(writer, "{}\t{}\t0", key, header_name(&headers.get_str(key).unwrap_or_default()))?;
+    }
+    writer.flush()?;
+    for suffix in ["_ss", "_h"] {
+        std::fs::copy(&lookup_file, format!("{}{}.lookup", db, suffix))?;
+    }
+    Ok(())
+}
+
+// Open a sequence database to find its entries by name
+// The lookup files are written first if they are missing or outdated, as in databases of older versions
+pub fn open_by_name(db: &str) -> Result<DbReader, Box<dyn std::error::Error>> {
+    let reader = DbReader::open(db)?;
+    if reader.has_lookup() { return Ok(reader); }
+    write_lookup(db)?;
+    DbReader::open(db)
+}
+
+// Writer of a Foldseek/MMseqs2 database
+pub struct DbWriter {
+    path: String,
+    data: BufWriter<File>,
+    index: Vec<(u32, usize, usize)>,
+    offset: usize,
+    dbtype: i32,
+}
+
+impl DbWriter {
+    pub fn create(path: &str, dbtype: i32) -> io::Result<DbWriter> {
+        Ok(DbWriter { path: path.to_string(), data: BufWriter::new(File::create(path)?), index: Vec::new(), offset: 0, dbtype })
+    }
+
+    pub fn write(&mut self, key: u32, entry: &[u8]) -> io::Result<()> {
+        self.data.write_all(entry)?;
+        self.data.write_all(&[0])?;
+        self.index.push((key, self.offset, entry.len() + 1));
+        self.offset += entry.len() + 1;
+        Ok(())
+    }
+
+    // Flush the data and write the index and dbtype files
+    pub fn finish(mut self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.sort_unstable();
+        let mut index_writer = BufWriter::new(File::create(format!("{}.index", self.path))?);
+        for (key, offset, length) in &self.index {
+            writeln!(index_writer, "{}\t{}\t{}", key, offset, length)?;
+        }
+        index_writer.flush()?;
+        std::fs::write(format!("{}.dbtype", self.path), self.dbtype.to_le_bytes())
+    }
+}
+
+// Writer of a database of amino acid and 3Di sequences, with its header database and lookup files
+// Sequences are keyed in the order they are added, as in 'foldseek createdb --shuffle 0'
+pub struct SeqDbWriter {
+    path: String,
+    aa: DbWriter,
+    ss: DbWriter,
+    h: DbWriter,
+    lookup: BufWriter<File>,
+    count: u32,
+}
+
+impl SeqDbWriter {
+    pub fn create(path: &str) -> io::Result<SeqDbWriter> {
+        Ok(SeqDbWriter {
+            path: path.to_string(),
+            aa: DbWriter::create(path, DBTYPE_AMINO_ACIDS)?,
+            ss: DbWriter::create(&format!("{}_ss", path), DBTYPE_AMINO_ACIDS)?,
+            h: DbWriter::create(&format!("{}_h", path), DBTYPE_GENERIC_DB)?,
+            lookup: BufWriter::new(File::create(format!("{}.lookup", path))?),
+            count: 0,
+        })
+    }
+
+    pub fn add(&mut self, name: &str, aa: &str, ss: &str) -> io::Result<()> {
+        let key = self.count;
+        self.aa.write(key, format!("{}\n", aa).as_bytes())?;
+        self.ss.write(key, format!("{}\n", ss).as_bytes())?;
+        self.h.write(key, format!("{}\n", name).as_bytes())?;
+        writeln!(self.lookup, "{}\t{}\t0", key, name)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.lookup.flush()?;
+        // The _ss and _h databases share the keys of the amino acid database
+        for suffix in ["_ss", "_h"] {
+            std::fs::copy(format!("{}.lookup", self.path), format!("{}{}.lookup", self.path, suffix))?;
+        }
+        self.aa.finish()?;
+        self.ss.finish()?;
+        self.h.finish()
+    }
+}
+
+// Remove the files of a database written by DbWriter
+pub fn remove_db(path: &str) -> io::Result<()> {
+    for suffix in ["", ".index", ".dbtype", ".lookup"] {
+        let file = format!("{}{}", path, suffix);
+        if Path::new(&file).exists() {
+            std::fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("unicore_foldseek_db_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("db").to_string_lossy().into_owned();
+
+        let mut writer = SeqDbWriter::create(&db).unwrap();
+        writer.add("unicore_a", "MKV", "DPV").unwrap();
+        writer.add("unicore_b", "MQQL", "DDPV").unwrap();
+        assert_eq!(writer.len(), 2);
+        writer.finish().unwrap();
+
+        let aa = DbReader::open(&db).unwrap();
+        let h = DbReader::open(&format!("{}_h", db)).unwrap();
+        let ss = DbReader::open(&format!("{}_ss", db)).unwrap();
+        assert_eq!(aa.len(), 2);
+        assert_eq!(aa.dbtype(), DBTYPE_AMINO_ACIDS);
+        assert_eq!(h.dbtype(), DBTYPE_GENERIC_DB);
+        assert_eq!(aa.keys().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(header_name(&h.get_str(1).unwrap()), "unicore_b");
+        assert_eq!(aa.get_str(1).unwrap(), "MQQL");
+        assert_eq!(ss.get_str(0).unwrap(), "DPV");
+        assert!(aa.get(2).is_none());
+
+        // Split database with non-sequential keys; offsets are global over the data files
+        let split = dir.join("split").to_string_lossy().into_owned();
+        std::fs::write(format!("{}.0", split), b"MKV\n\0").unwrap();
+        std::fs::write(format!("{}.1", split), b"MQQL\n\0").unwrap();
+        std::fs::write(format!("{}.index", split), "7\t5\t6\n3\t0\t5\n").unwrap();
+        let reader = DbReader::open(&split).unwrap();
+        assert_eq!(reader.keys().collect::<Vec<_>>(), vec![3, 7]);
+        assert_eq!(reader.get_str(3).unwrap(), "MKV");
+        assert_eq!(reader.get_str(7).unwrap(), "MQQL");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lookup() {
+        let dir = std::env::temp_dir().join(format!("unicore_foldseek_db_lookup_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("db").to_string_lossy().into_owned();
+
+        let mut writer = SeqDbWriter::create(&db).unwrap();
+        writer.add("unicore_a", "MKV", "DPV").unwrap();
+        writer.add("unicore_b", "MQQL", "DDPV").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(format!("{}.lookup", db)).unwrap(), "0\tunicore_a\t0\n1\tunicore_b\t0\n");
+        for suffix in ["", "_ss", "_h"] {
+            let reader = DbReader::open(&format!("{}{}", db, suffix)).unwrap();
+            assert!(reader.has_lookup());
+            assert_eq!(reader.key_of("unicore_b"), Some(1));
+            assert_eq!(reader.key_of("unicore_c"), None);
+        }
+
+        // A lookup file not matching the index is ignored, and rewritten from the headers
+        std::fs::write(format!("{}.lookup", db), "0\tunicore_a\t0\n").unwrap();
+        assert!(!DbReader::open(&db).unwrap().has_lookup());
+        let reader = open_by_name(&db).unwrap();
+        assert_eq!(reader.key_of("unicore_a"), Some(0));
+        assert_eq!(reader.get_str(reader.key_of("unicore_b").unwrap()).unwrap(), "MQQL");
+        assert_eq!(DbReader::open(&format!("{}_h", db)).unwrap().key_of("unicore_b"), Some(1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use crate::envs::error_handler as err;
+use crate::seq::fasta_io;
+use crate::seq::translate;
+
+// CDS feature of GFF3, possibly split into several segments
+struct Cds {
+    seqid: String,
+    strand: char,
+    // (start, end, phase) with 1-based inclusive coordinates
+    segments: Vec<(usize, usize, usize)>,
+    table: Option<u8>,
+    partial_5p: bool,
+    pseudo: bool,
+}
+
+// Decode percent-encoded characters of GFF3 attribute values
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_attributes(column: &str) -> HashMap<String, String> {
+    column.split(';')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), decode(v.trim())))
+        .collect()
+}
+
+// Read CDS features from GFF3 and translate them into protein sequences
+// Genome sequences are taken from the given fasta file, or from the ##FASTA section of the GFF3 file
+// Returns the list of (CDS identifier, protein sequence)
+pub fn read_gff_proteins(gff: &str, genome: Option<&str>, genetic_code: u8) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let reader = fasta_io::open_reader(gff)?;
+    let mut order: Vec<String> = Vec::new();
+    let mut features: HashMap<String, Cds> = HashMap::new();
+    let mut embedded: HashMap<String, String> = HashMap::new();
+    let mut in_fasta = false;
+    let mut curr_seqid = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        if in_fasta {
+            if let Some(header) = line.strip_prefix('>') {
+                curr_seqid = header.split_whitespace().next().unwrap_or("").to_string();
+            } else {
+                embedded.entry(curr_seqid.clone()).or_default().push_str(line.trim());
+            }
+            continue;
+        }
+        if line.starts_with("##FASTA") { in_fasta = true; continue; }
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 9 {
+            return Err(format!("Invalid GFF3 line in {}: {}", gff, line).into());
+        }
+        if cols[2] != "CDS" { continue; }
+        let start: usize = cols[3].parse().map_err(|_| format!("Invalid start position in {}: {}", gff, line))?;
+        let end: usize = cols[4].parse().map_err(|_| format!("Invalid end position in {}: {}", gff, line))?;
+        let phase: usize = cols[7].parse().unwrap_or(0);
+        let strand = cols[6].chars().next().unwrap_or('+');
+        let attrs = parse_attributes(cols[8]);
+
+        // Segments of the same CDS share the ID (or the locus tag or parent if the ID is not given)
+        let id = attrs.get("ID")
+            .or(attrs.get("locus_tag"))
+            .or(attrs.get("Parent"))
+            .cloned()
+            .unwrap_or_else(|| format!("{}:{}-{}", cols[0], start, end));
+        let cds = features.entry(id.clone()).or_insert_with(|| {
+            order.push(id.clone());
+            Cds {
+                seqid: cols[0].to_string(),
+                strand,
+                segments: Vec::new(),
+                table: attrs.get("transl_table").and_then(|t| t.parse().ok()),
+                partial_5p: false,
+                pseudo: attrs.get("pseudo").is_some_and(|p| p == "true"),
+            }
+        });
+        cds.segments.push((start, end, phase));
+        if (strand == '-' && attrs.contains_key("end_range")) || (strand != '-' && attrs.contains_key("start_range")) {
+            cds.partial_5p = true;
+        }
+    }
+
+    // Load genome sequences, keyed by the first word of the header
+    let genome_seqs: HashMap<String, String> = match genome {
+        Some(file) => fasta_io::read_fasta(file)?.into_iter()
+            .map(|(h, s)| (h.split_whitespace().next().unwrap_or("").to_string(), s))
+            .collect(),
+        None => embedded,
+    };
+    if genome_seqs.is_empty() {
+        return Err(format!("No genome sequences found for {}", gff).into());
+    }
+
+    let mut proteins = Vec::with_capacity(order.len());
+    for id in order {
+        let mut cds = features.remove(&id).unwrap();
+        if cds.pseudo { continue; }
+        let Some(contig) = genome_seqs.get(&cds.seqid) else {
+            err::warning(err::WRN_GENERAL, Some(format!("Sequence {} of CDS {} not found in the genome; skipping", cds.seqid, id)));
+            continue;
+        };
+
+        // Order the segments in the direction of transcription
+        if cds.strand == '-' {
+            cds.segments.sort_by_key(|s| std::cmp::Reverse(s.0));
+        } else {
+            cds.segments.sort_by_key(|s| s.0);
+        }
+        if cds.segments.iter().any(|&(s, e, _)| s == 0 || s > e || e > contig.len()) {
+            err::warning(err::WRN_GENERAL, Some(format!("CDS {} is out of the bounds of {}; skipping", id, cds.seqid)));
+            continue;
+        }
+        let mut nucl = String::new();
+        for &(s, e, _) in &cds.segments {
+            let segment = &contig[s - 1..e];
+            if cds.strand == '-' { nucl.push_str(&translate::reverse_complement(segment)); } else { nucl.push_str(segment); }
+        }
+
+        let phase = cds.segments[0].2.min(nucl.len());
+        let table = cds.table.unwrap_or(genetic_code);
+        match translate::translate(&nucl[phase..], table, phase == 0 && !cds.partial_5p) {
+            Some(protein) => proteins.push((id, protein)),
+            None => err::error(err::ERR_GENERAL, Some(format!("Unsupported genetic code {} for CDS {} in {}", table, id, gff))),
+        }
+    }
+
+    Ok(proteins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_gff_proteins() {
+        // cds1 is on the minus strand, split into two segments, and starts with phase 1
+        // cds2 starts with GTG, read as methionine in the bacterial genetic code
+        let gff = "##gff-version 3\n\
+            chr1\ttest\tCDS\t23\t32\t.\t-\t1\tID=cds1;Parent=mrna1\n\
+            chr1\ttest\tCDS\t6\t14\t.\t-\t0\tID=cds1;Parent=mrna1\n\
+            chr2\ttest\tCDS\t3\t11\t.\t+\t0\tID=cds%2C2;transl_table=11\n\
+            chr2\ttest\tCDS\t1\t3\t.\t+\t0\tID=cds3;pseudo=true\n\
+            ##FASTA\n\
+            >chr1 test\nGGGGGTTAGCCAAACCCCCCCC\nCCATTTCATGGGGGG\n\
+            >chr2\nAAGTGAAATGA\n";
+        let file = std::env::temp_dir().join(format!("unicore_gff_io_{}.gff3", std::process::id()));
+        std::fs::write(&file, gff).unwrap();
+        let proteins = read_gff_proteins(&file.to_string_lossy(), None, 1).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(proteins, vec![("cds1".to_string(), "MKWFG".to_string()), ("cds,2".to_string(), "MK".to_string())]);
+    }
+}
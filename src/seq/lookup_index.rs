@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, MAIN_SEPARATOR as SEP};
+
+use memmap2::Mmap;
+
+use crate::seq::bucket;
+
+// Binary index of a lookup table, sorted by the MD5 hash of the sequences
+// Layout: magic, version (u32), number of entries (u64),
+// entries of (MD5 digest (16 bytes), offset (u64), 3Di length (u32), accession length (u32), pLDDT (f32, NaN if unknown)),
+// and the concatenated 3Di sequences and accessions
+const MAGIC: &[u8; 4] = b"UCLI";
+pub const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 36;
+
+// Entry of a lookup table: 3Di sequence, and the accession and mean pLDDT of the model if known
+// Table lines are (MD5 hash, 3Di sequence[, accession[, pLDDT]]) separated by tabs
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableEntry {
+    pub ss: String,
+    pub accession: Option<String>,
+    pub plddt: Option<f32>,
+}
+
+// Parse a line of a lookup table into the hash and the entry
+pub fn parse_table_line(line: &str) -> Option<(&str, TableEntry)> {
+    let mut split = line.split('\t');
+    let hash = split.next()?;
+    let ss = split.next()?.to_string();
+    let accession = split.next().filter(|a| !a.is_empty() && *a != "-").map(|a| a.to_string());
+    let plddt = split.next().and_then(|p| p.parse().ok());
+    Some((hash, TableEntry { ss, accession, plddt }))
+}
+
+// Load a lookup table to memory, keyed by the hexadecimal MD5 hash
+// The last entry of a hash is kept, as in the binary index
+pub fn load_table(table: &str) -> Result<HashMap<String, TableEntry>, Box<dyn std::error::Error>> {
+    let mut table_map = HashMap::new();
+    for line in BufReader::new(File::open(table)?).lines() {
+        let line = line?;
+        if let Some((hash, entry)) = parse_table_line(&line) {
+            table_map.insert(hash.to_string(), entry);
+        }
+    }
+    Ok(table_map)
+}
+
+pub fn index_file(dir: &str, index: usize) -> String {
+    format!("{}{}{:02x}.idx", dir, SEP, index)
+}
+
+// Check if every table has a binary index
+pub fn index_complete(dir: &str) -> bool {
+    (0..bucket::BUCKETS).all(|i| Path::new(&index_file(dir, i)).exists())
+}
+
+// Digest of a hexadecimal MD5 hash
+fn digest(hash: &str) -> Option<[u8; 16]> {
+    if hash.len() != 32 { return None; }
+    let mut digest = [0; 16];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hash.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+// Build the binary index of a TSV lookup table of (MD5 hash, 3Di sequence) and return the number of entries
+// The index is written to a temporary file first, so that an interrupted build leaves no index behind
+pub fn build_index(table: &str, index: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut entries: Vec<([u8; 16], TableEntry)> = Vec::new();
+    for line in BufReader::new(File::open(table)?).lines() {
+        let line = line?;
+        if line.is_empty() { continue; }
+        let (hash, entry) = parse_table_line(&line).ok_or(format!("Invalid line in {}: {}", table, line))?;
+        let key = digest(hash).ok_or(format!("Invalid MD5 hash in {}: {}", table, hash))?;
+        entries.push((key, entry));
+    }
+    // The last entry of a hash is kept, as when the table is loaded to memory; the stable sort keeps the reversed order of duplicates
+    entries.reverse();
+    entries.sort_by_key(|e| e.0);
+    entries.dedup_by(|a, b| a.0 == b.0);
+
+    let part = format!("{}.part", index);
+    let mut writer = BufWriter::new(File::create(&part)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+    let mut offset = 0u64;
+    for (key, entry) in &entries {
+        let accession = entry.accession.as_deref().unwrap_or("");
+        writer.write_all(key)?;
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&(entry.ss.len() as u32).to_le_bytes())?;
+        writer.write_all(&(accession.len() as u32).to_le_bytes())?;
+        writer.write_all(&entry.plddt.unwrap_or(f32::NAN).to_le_bytes())?;
+        offset += (entry.ss.len() + accession.len()) as u64;
+    }
+    for (_, entry) in &entries {
+        writer.write_all(entry.ss.as_bytes())?;
+        writer.write_all(entry.accession.as_deref().unwrap_or("").as_bytes())?;
+    }
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&part, index)?;
+    Ok(entries.len())
+}
+
+// Memory-mapped binary index of a lookup table
+pub struct LookupIndex {
+    mmap: Mmap,
+    count: usize,
+}
+
+impl LookupIndex {
+    pub fn open(index: &str) -> Result<LookupIndex, Box<dyn std::error::Error>> {
+        let file = File::open(index)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_SIZE || &mmap[..4] != MAGIC {
+            return Err(format!("{} is not a lookup index", index).into());
+        }
+        let version = u32::from_le_bytes(mmap[4..8].try_into()?);
+        if version != VERSION {
+            return Err(format!("Lookup index {} has version {}, but version {} is required. Please rebuild it with 'unicore lookup-index'", index, version, VERSION).into());
+        }
+        let count = u64::from_le_bytes(mmap[8..16].try_into()?) as usize;
+        if mmap.len() < HEADER_SIZE + count * ENTRY_SIZE {
+            return Err(format!("Lookup index {} is truncated", index).into());
+        }
+        Ok(LookupIndex { mmap, count })
+    }
+
+    // Entry of the sequence with the given hexadecimal MD5 hash
+    pub fn get(&self, hash: &str) -> Option<TableEntry> {
+        let key = digest(hash)?;
+        let entry = |i: usize| &self.mmap[HEADER_SIZE + i * ENTRY_SIZE..HEADER_SIZE + (i + 1) * ENTRY_SIZE];
+        let (mut lo, mut hi) = (0, self.count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let e = entry(mid);
+            match e[..16].cmp(&key) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    let offset = u64::from_le_bytes(e[16..24].try_into().ok()?) as usize;
+                    let ss_length = u32::from_le_bytes(e[24..28].try_into().ok()?) as usize;
+                    let accession_length = u32::from_le_bytes(e[28..32].try_into().ok()?) as usize;
+                    let plddt = f32::from_le_bytes(e[32..36].try_into().ok()?);
+                    let start = HEADER_SIZE + self.count * ENTRY_SIZE + offset;
+                    let ss = std::str::from_utf8(self.mmap.get(start..start + ss_length)?).ok()?;
+                    let accession = std::str::from_utf8(self.mmap.get(start + ss_length..start + ss_length + accession_length)?).ok()?;
+                    return Some(TableEntry {
+                        ss: ss.to_string(),
+                        accession: if accession.is_empty() { None } else { Some(accession.to_string()) },
+                        plddt: if plddt.is_nan() { None } else { Some(plddt) },
+                    });
+                },
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_index() {
+        let dir = std::env::temp_dir().join(format!("unicore_lookup_index_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let table = dir.join("00.tsv").to_string_lossy().into_owned();
+        let index = dir.join("00.idx").to_string_lossy().into_owned();
+        std::fs::write(&table, "00ff0000000000000000000000000000\tDDPV\tAF-P12345-F1\t87.5\n0000000000000000000000000000000a\tVL\n").unwrap();
+
+        assert_eq!(build_index(&table, &index).unwrap(), 2);
+        let lookup = LookupIndex::open(&index).unwrap();
+        let entry = TableEntry { ss: "DDPV".to_string(), accession: Some("AF-P12345-F1".to_string()), plddt: Some(87.5) };
+        assert_eq!(lookup.get("00ff0000000000000000000000000000"), Some(entry));
+        let entry = TableEntry { ss: "VL".to_string(), accession: None, plddt: None };
+        assert_eq!(lookup.get("0000000000000000000000000000000A"), Some(entry));
+        assert_eq!(lookup.get("0000000000000000000000000000000b"), None);
+        assert_eq!(lookup.get("not a hash"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_hash() {
+        let dir = std::env::temp_dir().join(format!("unicore_lookup_index_duplicate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let table = dir.join("00.tsv").to_string_lossy().into_owned();
+        let index = dir.join("00.idx").to_string_lossy().into_owned();
+        let hash = "00ff0000000000000000000000000000";
+        std::fs::write(&table, format!("{}\tDDPV\tAF-P12345-F1\n0000000000000000000000000000000a\tVL\n{}\tDDPL\tAF-Q67890-F1\t70\n", hash, hash)).unwrap();
+
+        // Both the index and the table in memory keep the last entry of the hash
+        let entry = TableEntry { ss: "DDPL".to_string(), accession: Some("AF-Q67890-F1".to_string()), plddt: Some(70.0) };
+        assert_eq!(build_index(&table, &index).unwrap(), 2);
+        assert_eq!(LookupIndex::open(&index).unwrap().get(hash), Some(entry.clone()));
+        let table_map = load_table(&table).unwrap();
+        assert_eq!(table_map.len(), 2);
+        assert_eq!(table_map.get(hash), Some(&entry));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
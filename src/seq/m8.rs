@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// Hit tables in the m8 format written by Foldseek and MMseqs2 convertalis
+// Columns are given by --format-output, or by the header line of --format-mode 4
+pub const DEFAULT_FORMAT: &str = "query,target,fident,alnlen,mismatch,gapopen,qstart,qend,tstart,tend,evalue,bits";
+
+// Column positions of an m8 format
+pub struct Format {
+    columns: HashMap<String, usize>,
+    query: usize,
+    target: usize,
+}
+
+impl Format {
+    // Parse a comma separated list of columns as given to --format-output
+    pub fn parse(format: &str) -> Result<Format, String> {
+        let columns: HashMap<String, usize> = format.split(',').map(|c| c.trim().to_string()).enumerate().map(|(i, c)| (c, i)).collect();
+        match (columns.get("query"), columns.get("target")) {
+            (Some(&query), Some(&target)) => Ok(Format { query, target, columns }),
+            _ => Err(format!("Format `{}` must have the query and target columns", format)),
+        }
+    }
+
+    // Format declared by a header line, which names the query and target columns
+    pub fn from_header(line: &str) -> Option<Format> {
+        let cols: Vec<&str> = line.split('\t').collect();
+        if !cols.contains(&"query") || !cols.contains(&"target") { return None; }
+        Format::parse(&cols.join(",")).ok()
+    }
+
+    fn column(&self, name: &str) -> Option<usize> {
+        self.columns.get(name).copied()
+    }
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format::parse(DEFAULT_FORMAT).unwrap()
+    }
+}
+
+// Score columns a hit keeps for filtering
+const SCORE_COLUMNS: [&str; 7] = ["evalue", "bits", "qcov", "tcov", "alntmscore", "qtmscore", "ttmscore"];
+
+pub struct Hit {
+    pub query: String,
+    pub target: String,
+    // Raw values of the score columns in the line, parsed only when a filter needs them
+    scores: Vec<(&'static str, String)>,
+    // Original line, to write the hit out unchanged
+    pub line: String,
+}
+
+impl Hit {
+    // Value of a score column; None if the line lacks the column, and an error if it is not a number
+    pub fn score(&self, name: &str) -> Result<Option<f64>, String> {
+        match self.scores.iter().find(|(column, _)| *column == name) {
+            Some((_, value)) => value.trim().parse::<f64>().map(Some).map_err(|_| format!("Invalid {} '{}' in line: {}", name, value, self.line)),
+            None => Ok(None),
+        }
+    }
+
+    // TM-score of the alignment; alntmscore, or else qtmscore or ttmscore
+    pub fn tmscore(&self) -> Result<Option<f64>, String> {
+        for name in ["alntmscore", "qtmscore", "ttmscore"] {
+            if let Some(score) = self.score(name)? { return Ok(Some(score)); }
+        }
+        Ok(None)
+    }
+}
+
+// Parse a line of the format; score columns missing from the line are left empty,
+// so that a two column cluster tsv file reads as hits without scores
+// Columns are separated by tabs, or by whitespace in lines without a tab, e.g. hand-made cluster files
+pub fn parse_hit(line: &str, format: &Format) -> Result<Hit, String> {
+    let cols: Vec<&str> = if line.contains('\t') { line.split('\t').collect() } else { line.split_whitespace().collect() };
+    let (Some(query), Some(target)) = (cols.get(format.query), cols.get(format.target)) else {
+        return Err(format!("Missing query or target column in line: {}", line));
+    };
+    let scores = SCORE_COLUMNS.iter()
+        .filter_map(|&name| format.column(name).and_then(|i| cols.get(i)).map(|value| (name, value.to_string())))
+        .collect();
+    Ok(Hit { query: query.to_string(), target: target.to_string(), scores, line: line.to_string() })
+}
+
+// Reader of the hits of an m8 file in order; a header line overrides the given format
+pub struct HitReader {
+    lines: std::io::Lines<BufReader<File>>,
+    format: Format,
+    // Header line, if the file has one
+    pub header: Option<String>,
+    // First line, if it was not a header
+    first: Option<String>,
+    file: String,
+}
+
+impl HitReader {
+    pub fn open(file: &str, format: Option<Format>) -> Result<HitReader, Box<dyn std::error::Error>> {
+        let mut lines = BufReader::new(File::open(file)?).lines();
+        let mut first = lines.next().transpose()?;
+        let mut header = None;
+        let format = match first.as_deref().and_then(Format::from_header) {
+            Some(declared) => { header = first.take(); declared },
+            None => format.unwrap_or_default(),
+        };
+        Ok(HitReader { lines, format, header, first, file: file.to_string() })
+    }
+}
+
+impl Iterator for HitReader {
+    type Item = Result<Hit, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.first.take() {
+                Some(line) => line,
+                None => match self.lines.next()? {
+                    Ok(line) => line,
+                    Err(e) => return Some(Err(e.into())),
+                },
+            };
+            if line.is_empty() { continue; }
+            return Some(parse_hit(&line, &self.format).map_err(|e| format!("{}: {}", self.file, e).into()));
+        }
+    }
+}
+
+// Read the hits of an m8 file in order, with its header line
+pub fn read_hits(file: &str, format: Option<Format>) -> Result<(Vec<Hit>, Option<String>), Box<dyn std::error::Error>> {
+    let mut reader = HitReader::open(file, format)?;
+    let header = reader.header.take();
+    Ok((reader.collect::<Result<Vec<Hit>, _>>()?, header))
+}
+
+// Minimum score, coverage and maximum e-value of the hits to keep
+#[derive(Default)]
+pub struct Filter {
+    pub min_bits: Option<f64>,
+    pub max_evalue: Option<f64>,
+    // Minimum fraction of both the query and the target covered, as far as the format has them
+    pub min_coverage: Option<f64>,
+    pub min_tmscore: Option<f64>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.min_bits.is_none() && self.max_evalue.is_none() && self.min_coverage.is_none() && self.min_tmscore.is_none()
+    }
+
+    // Check if the hit passes the filter; fails if the hit lacks a column the filter needs
+    pub fn accepts(&self, hit: &Hit) -> Result<bool, String> {
+        let value = |value: Option<f64>, name: &str| value.ok_or_else(|| format!("Filtering by {} needs the {} column, missing in line: {}", name, name, hit.line));
+        if let Some(min) = self.min_bits {
+            if value(hit.score("bits")?, "bits")? < min { return Ok(false); }
+        }
+        if let Some(max) = self.max_evalue {
+            if value(hit.score("evalue")?, "evalue")? > max { return Ok(false); }
+        }
+        if let Some(min) = self.min_coverage {
+            let (qcov, tcov) = (hit.score("qcov")?, hit.score("tcov")?);
+            if qcov.is_none() && tcov.is_none() {
+                return Err(format!("Filtering by coverage needs the qcov or tcov column, missing in line: {}", hit.line));
+            }
+            if qcov.is_some_and(|c| c < min) || tcov.is_some_and(|c| c < min) { return Ok(false); }
+        }
+        if let Some(min) = self.min_tmscore {
+            if value(hit.tmscore()?, "alntmscore")? < min { return Ok(false); }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hit() {
+        let hit = parse_hit("q1\tt1\t0.5\t100\t0\t0\t1\t100\t1\t100\t1e-10\t250", &Format::default()).unwrap();
+        assert_eq!((hit.score("evalue"), hit.score("bits"), hit.score("qcov")), (Ok(Some(1e-10)), Ok(Some(250.0)), Ok(None)));
+        // Cluster tsv files have no scores, and may be separated by spaces
+        let hit = parse_hit("q1 t1", &Format::default()).unwrap();
+        assert_eq!((hit.query.as_str(), hit.target.as_str(), hit.score("bits")), ("q1", "t1", Ok(None)));
+        // Scores are only parsed when filtering by them
+        let hit = parse_hit("q1\tt1\tcustom\t0\t0\t0\t1\t100\t1\t100\tn/a\t250", &Format::default()).unwrap();
+        assert_eq!(Filter::default().accepts(&hit), Ok(true));
+        assert!(Filter { max_evalue: Some(0.1), ..Default::default() }.accepts(&hit).is_err());
+
+        let format = Format::from_header("query\ttarget\tqcov\ttcov\tqtmscore\tevalue").unwrap();
+        let hit = parse_hit("q1\tt1\t0.9\t0.6\t0.7\t0.001", &format).unwrap();
+        assert_eq!((hit.score("qcov"), hit.score("tcov"), hit.tmscore()), (Ok(Some(0.9)), Ok(Some(0.6)), Ok(Some(0.7))));
+        assert!(Format::from_header("q1\tt1\t0.9").is_none());
+        assert!(Format::parse("query,evalue").is_err());
+        let swapped = parse_hit("t1\tq1\t12", &Format::parse("target,query,bits").unwrap()).unwrap();
+        assert_eq!((swapped.query.as_str(), swapped.score("bits")), ("q1", Ok(Some(12.0))));
+
+        let filter = Filter { min_coverage: Some(0.5), min_tmscore: Some(0.5), ..Default::default() };
+        assert_eq!(filter.accepts(&hit), Ok(true));
+        let filter = Filter { min_coverage: Some(0.8), ..Default::default() };
+        assert_eq!(filter.accepts(&hit), Ok(false));
+        let filter = Filter { min_bits: Some(50.0), ..Default::default() };
+        assert!(filter.accepts(&hit).is_err());
+    }
+}
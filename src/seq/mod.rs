@@ -1,4 +1,16 @@
 pub mod combine_fasta;
 pub mod create_gene_specific_fasta;
 pub mod fasta_io;
-pub mod afdb_lookup;
\ No newline at end of file
+pub mod afdb_lookup;
+pub mod translate;
+pub mod gff_io;
+pub mod proteome;
+pub mod flatfile_io;
+pub mod bucket;
+pub mod foldseek_db;
+pub mod lookup_index;
+pub mod prediction_cache;
+pub mod validate;
+pub mod cluster_diff;
+pub mod m8;
+pub mod orthology;
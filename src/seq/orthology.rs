@@ -0,0 +1,72 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::seq::m8::Hit;
+
+// Reciprocal best hits between reference genes and the genes of each species
+// The forward hits go from the reference genes to the genes, the reverse hits back from the genes to the reference genes
+// For each reference gene and species, the best scoring gene is an ortholog if its own best hit is the reference gene;
+// other genes of the species scoring at least ratio times as high are kept as in-paralogs, if their best hit is the reference gene too
+// Returns whether each forward hit is kept
+// Hits without a valid bit score never count as best hits
+pub fn reciprocal_best_hits(forward: &[Hit], reverse: &[Hit], species: &HashMap<String, Vec<String>>, ratio: f64) -> Vec<bool> {
+    let bits = |hit: &Hit| hit.score("bits").ok().flatten().unwrap_or(f64::MIN);
+    // Best reference genes of each gene, with ties
+    let mut best_reverse: HashMap<&str, (f64, HashSet<&str>)> = HashMap::new();
+    for hit in reverse {
+        let best = best_reverse.entry(&hit.query).or_insert((f64::MIN, HashSet::new()));
+        if bits(hit) > best.0 {
+            *best = (bits(hit), HashSet::from([hit.target.as_str()]));
+        } else if bits(hit) == best.0 {
+            best.1.insert(&hit.target);
+        }
+    }
+    let reciprocal = |hit: &Hit| best_reverse.get(hit.target.as_str()).is_some_and(|(_, refs)| refs.contains(hit.query.as_str()));
+    let species_of = |hit: &Hit| species.get(&hit.target).into_iter().flatten().map(|s| s.as_str());
+
+    // Best score of each reference gene in each species
+    let mut best_forward: HashMap<(&str, &str), f64> = HashMap::new();
+    for hit in forward {
+        for spe in species_of(hit) {
+            let best = best_forward.entry((&hit.query, spe)).or_insert(f64::MIN);
+            *best = best.max(bits(hit));
+        }
+    }
+    // Reference gene and species pairs whose best scoring gene is a reciprocal best hit
+    let mut orthologous: HashSet<(&str, &str)> = HashSet::new();
+    for hit in forward.iter().filter(|hit| reciprocal(hit)) {
+        for spe in species_of(hit) {
+            if bits(hit) == best_forward[&(hit.query.as_str(), spe)] {
+                orthologous.insert((&hit.query, spe));
+            }
+        }
+    }
+
+    forward.iter().map(|hit| {
+        reciprocal(hit) && species_of(hit).any(|spe| {
+            orthologous.contains(&(hit.query.as_str(), spe)) && bits(hit) >= ratio * best_forward[&(hit.query.as_str(), spe)]
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seq::m8::{parse_hit, Format};
+
+    fn hits(rows: &[(&str, &str, f64)]) -> Vec<Hit> {
+        rows.iter().map(|(q, t, bits)| parse_hit(&format!("{}\t{}\t1.0\t100\t0\t0\t1\t100\t1\t100\t1e-10\t{}", q, t, bits), &Format::default()).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_reciprocal_best_hits() {
+        let species: HashMap<String, Vec<String>> = [("a1", "A"), ("a2", "A"), ("a3", "A"), ("b1", "B"), ("b2", "B")]
+            .iter().map(|(g, s)| (g.to_string(), vec![s.to_string()])).collect();
+        let forward = hits(&[("r1", "a1", 100.0), ("r1", "a2", 95.0), ("r1", "a3", 50.0), ("r1", "b1", 80.0), ("r2", "b1", 70.0), ("r2", "b2", 60.0)]);
+        // b1 is closer to r1, and b2 closer to another reference gene
+        let reverse = hits(&[("a1", "r1", 100.0), ("a2", "r1", 95.0), ("a3", "r1", 50.0), ("b1", "r1", 80.0), ("b1", "r2", 70.0), ("b2", "r3", 90.0), ("b2", "r2", 60.0)]);
+
+        assert_eq!(reciprocal_best_hits(&forward, &reverse, &species, 1.0), vec![true, false, false, true, false, false]);
+        // a2 is an in-paralog of a1 within 90% of its score
+        assert_eq!(reciprocal_best_hits(&forward, &reverse, &species, 0.9), vec![true, true, false, true, false, false]);
+    }
+}
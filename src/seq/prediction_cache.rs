@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR as SEP};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::seq::afdb_lookup::table_hash;
+
+// Environment variable for the cache directory
+pub const CACHE_ENV: &str = "UNICORE_CACHE";
+// Temporary files older than this are left over from interrupted writes
+const STALE_TMP_SECS: u64 = 3600;
+
+// Cache directory given by the option or the environment variable
+pub fn cache_dir(option: Option<String>) -> Option<String> {
+    option.or_else(|| std::env::var(CACHE_ENV).ok().filter(|d| !d.is_empty()))
+}
+
+// Content-addressed cache of predicted 3Di sequences, shared by several projects
+// Each entry is a file named after the MD5 hash of the amino acid sequence, in a subdirectory by its first byte
+// Entries are written to a temporary file and renamed into place, so concurrent runs never read a partial entry
+// Cache entry as (path, size in bytes, last use)
+type Entry = (PathBuf, u64, SystemTime);
+
+pub struct PredictionCache {
+    dir: String,
+}
+
+impl PredictionCache {
+    pub fn open(dir: &str) -> io::Result<PredictionCache> {
+        std::fs::create_dir_all(dir)?;
+        Ok(PredictionCache { dir: dir.to_string() })
+    }
+
+    fn entry_file(&self, hash: &str) -> String {
+        format!("{}{}{}{}{}", self.dir, SEP, &hash[..2], SEP, hash)
+    }
+
+    // 3Di sequence of the amino acid sequence, if cached
+    // Hits are touched so that pruning removes the least recently used entries first
+    pub fn get(&self, seq: &str) -> Option<String> {
+        let file = self.entry_file(&table_hash(seq));
+        let ss = std::fs::read_to_string(&file).ok()?;
+        let ss = ss.trim_end();
+        if ss.len() != seq.len() { return None; }
+        if let Ok(f) = File::options().write(true).open(&file) {
+            let _ = f.set_modified(SystemTime::now());
+        }
+        Some(ss.to_string())
+    }
+
+    // Store the 3Di sequence of the amino acid sequence; existing entries are kept
+    pub fn put(&self, seq: &str, ss: &str) -> io::Result<()> {
+        let hash = table_hash(seq);
+        let file = self.entry_file(&hash);
+        if Path::new(&file).exists() { return Ok(()); }
+        std::fs::create_dir_all(format!("{}{}{}", self.dir, SEP, &hash[..2]))?;
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let tmp = format!("{}.{}.{}.tmp", file, std::process::id(), nanos);
+        let mut writer = File::create(&tmp)?;
+        writeln!(writer, "{}", ss)?;
+        writer.sync_all()?;
+        std::fs::rename(&tmp, &file)
+    }
+
+    // Entries of the cache, and the temporary files left over from interrupted writes
+    fn entries(&self) -> io::Result<(Vec<Entry>, Vec<PathBuf>)> {
+        let (mut entries, mut stale) = (Vec::new(), Vec::new());
+        for sub in std::fs::read_dir(&self.dir)? {
+            let sub = sub?.path();
+            if !sub.is_dir() { continue; }
+            for entry in std::fs::read_dir(&sub)? {
+                let entry = entry?;
+                let metadata = match entry.metadata() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+                let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+                if entry.path().extension().is_some_and(|e| e == "tmp") {
+                    let age = SystemTime::now().duration_since(modified).map(|d| d.as_secs()).unwrap_or(0);
+                    if age > STALE_TMP_SECS { stale.push(entry.path()); }
+                    continue;
+                }
+                entries.push((entry.path(), metadata.len(), modified));
+            }
+        }
+        Ok((entries, stale))
+    }
+
+    // Number of entries and their total size in bytes
+    pub fn size(&self) -> io::Result<(usize, u64)> {
+        let (entries, _) = self.entries()?;
+        Ok((entries.len(), entries.iter().map(|e| e.1).sum()))
+    }
+
+    // Remove the entries unused for longer than max_age seconds, then the least recently used ones until the cache fits in max_size bytes
+    // Returns the number of removed entries and their total size in bytes
+    pub fn prune(&self, max_size: Option<u64>, max_age: Option<u64>) -> io::Result<(usize, u64)> {
+        let (mut entries, stale) = self.entries()?;
+        for tmp in stale {
+            let _ = std::fs::remove_file(tmp);
+        }
+        entries.sort_by_key(|e| e.2);
+        let now = SystemTime::now();
+        let mut total: u64 = entries.iter().map(|e| e.1).sum();
+        let (mut removed, mut removed_size) = (0, 0);
+        for (path, size, modified) in entries {
+            let expired = max_age.is_some_and(|age| now.duration_since(modified).map(|d| d.as_secs() > age).unwrap_or(false));
+            let oversized = max_size.is_some_and(|max| total > max);
+            if !expired && !oversized { continue; }
+            // Entries removed by a concurrent run are skipped
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    removed += 1;
+                    removed_size += size;
+                    total -= size;
+                },
+                Err(e) if e.kind() == io::ErrorKind::NotFound => { total -= size; },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((removed, removed_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prediction_cache() {
+        let dir = std::env::temp_dir().join(format!("unicore_cache_test_{}", std::process::id()));
+        let cache = PredictionCache::open(&dir.to_string_lossy()).unwrap();
+
+        assert_eq!(cache.get("MKV"), None);
+        cache.put("MKV", "DPL").unwrap();
+        cache.put("MKV", "VVV").unwrap();
+        cache.put("MKVL", "DPLV").unwrap();
+        assert_eq!(cache.get("MKV"), Some("DPL".to_string()));
+        assert_eq!(cache.size().unwrap(), (2, 9));
+
+        // The least recently used entry is removed first
+        assert_eq!(cache.prune(Some(5), None).unwrap(), (1, 5));
+        assert_eq!(cache.size().unwrap(), (1, 4));
+        assert_eq!(cache.get("MKVL"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
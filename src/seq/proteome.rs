@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::envs::error_handler as err;
+use crate::seq::fasta_io;
+use crate::seq::gff_io;
+use crate::seq::flatfile_io;
+
+// Recognized extensions of annotation files and nucleotide genome files
+pub const GFF_EXTENSIONS: [&str; 2] = ["gff", "gff3"];
+pub const FLATFILE_EXTENSIONS: [&str; 5] = ["gb", "gbk", "gbff", "genbank", "embl"];
+pub const GENOME_EXTENSIONS: [&str; 1] = ["fna"];
+
+fn is_gff(path: &Path) -> bool {
+    fasta_io::format_extension(path).is_some_and(|ext| GFF_EXTENSIONS.contains(&ext.as_str()))
+}
+fn is_flatfile(path: &Path) -> bool {
+    fasta_io::format_extension(path).is_some_and(|ext| FLATFILE_EXTENSIONS.contains(&ext.as_str()))
+}
+fn is_genome(path: &Path) -> bool {
+    fasta_io::format_extension(path).is_some_and(|ext| GENOME_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// Where the protein sequences of a species come from
+pub enum Source {
+    // Protein fasta file
+    Fasta(String),
+    // GFF3 annotation with the genome fasta file; genome is embedded in GFF3 if not given
+    Gff { gff: String, genome: Option<String> },
+    // GenBank or EMBL flat file
+    FlatFile(String),
+}
+
+pub struct Proteome {
+    pub species: String,
+    pub source: Source,
+    // (column, value) pairs given in the sample sheet, e.g. taxonomy ID
+    pub metadata: Vec<(String, String)>,
+    // Precomputed 3Di fasta file with the same headers as the amino acid sequences
+    pub ss: Option<String>,
+}
+
+// Iterator over the protein sequences of a proteome as (gene name, sequence)
+pub type Records = Box<dyn Iterator<Item = std::io::Result<(String, String)>>>;
+
+impl Proteome {
+    // Protein sequences as (gene name, sequence); fasta files are streamed record by record
+    pub fn records(&self, genetic_code: u8) -> Result<Records, Box<dyn std::error::Error>> {
+        match &self.source {
+            Source::Fasta(file) => Ok(Box::new(fasta_io::fasta_records(file)?)),
+            Source::Gff { gff, genome } => Ok(Box::new(gff_io::read_gff_proteins(gff, genome.as_deref(), genetic_code)?.into_iter().map(Ok))),
+            Source::FlatFile(file) => Ok(Box::new(flatfile_io::read_flatfile_proteins(file, genetic_code)?.into_iter().map(Ok))),
+        }
+    }
+    pub fn file(&self) -> &str {
+        match &self.source {
+            Source::Fasta(file) => file,
+            Source::Gff { gff, .. } => gff,
+            Source::FlatFile(file) => file,
+        }
+    }
+}
+
+fn source_of(file: &str, genome: Option<String>) -> Source {
+    let path = Path::new(file);
+    if is_gff(path) {
+        Source::Gff { gff: file.to_string(), genome }
+    } else if is_flatfile(path) {
+        Source::FlatFile(file.to_string())
+    } else {
+        Source::Fasta(file.to_string())
+    }
+}
+
+// Check if the species label can be used as a tip label of Newick tree
+pub fn is_newick_safe(label: &str) -> bool {
+    !label.is_empty() && !label.chars().any(|c| c.is_whitespace() || "()[]:;,'\"".contains(c))
+}
+
+// Read the sample sheet, a TSV file with a header line
+// Columns 'file' and 'species' are mandatory; 'genome' gives the genome fasta of a GFF3 file,
+// and '3di' gives the precomputed 3Di fasta file
+// All other columns (e.g. 'taxid') are kept as metadata of the species
+// Relative file paths are resolved from the directory of the sample sheet
+fn read_sample_sheet(sheet: &str) -> Result<Vec<Proteome>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(sheet)?;
+    let base = Path::new(sheet).parent().unwrap_or(Path::new(""));
+    let resolve = |file: &str| -> String {
+        if Path::new(file).is_absolute() { file.to_string() } else { base.join(file).to_string_lossy().into_owned() }
+    };
+    // Lines are numbered before blank lines are skipped, to report them as in the file
+    let mut lines = content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let header: Vec<String> = lines.next().map_or("", |(_, l)| l).trim_start_matches('#').split('\t').map(|c| c.trim().to_lowercase()).collect();
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let (Some(file_col), Some(species_col)) = (column("file"), column("species")) else {
+        return Err(format!("Sample sheet {} must have 'file' and 'species' columns in the header", sheet).into());
+    };
+    let genome_col = column("genome");
+    let ss_col = column("3di");
+
+    let mut proteomes: Vec<Proteome> = Vec::new();
+    for (i, line) in lines {
+        let cols: Vec<&str> = line.split('\t').map(|c| c.trim()).collect();
+        if cols.len() != header.len() {
+            return Err(format!("Line {} of sample sheet {} has {} columns, expected {}", i + 1, sheet, cols.len(), header.len()).into());
+        }
+        let species = cols[species_col].to_string();
+        if !is_newick_safe(&species) {
+            return Err(format!("Species label '{}' in sample sheet {} contains whitespace or characters reserved in Newick format", species, sheet).into());
+        }
+        let file = resolve(cols[file_col]);
+        if !Path::new(&file).is_file() {
+            return Err(format!("File {} of species {} does not exist", file, species).into());
+        }
+        let genome = genome_col.map(|c| cols[c]).filter(|g| !g.is_empty()).map(resolve);
+        let ss = ss_col.map(|c| cols[c]).filter(|f| !f.is_empty()).map(resolve);
+        if let Some(ss) = ss.as_ref().filter(|f| !Path::new(f).is_file()) {
+            return Err(format!("3Di file {} of species {} does not exist", ss, species).into());
+        }
+        let metadata = header.iter().zip(cols.iter()).enumerate()
+            .filter(|(c, _)| *c != file_col && *c != species_col && Some(*c) != genome_col && Some(*c) != ss_col)
+            .map(|(_, (k, v))| (k.clone(), v.to_string()))
+            .collect();
+        proteomes.push(Proteome { species, source: source_of(&file, genome), metadata, ss });
+    }
+
+    Ok(proteomes)
+}
+
+// Collect the input proteomes from a directory, a sample sheet (.tsv) or a single file
+// A GFF3 file is paired with the fasta file of the same species name, which is then read as its genome
+pub fn collect_proteomes(input: &str) -> Result<Vec<Proteome>, Box<dyn std::error::Error>> {
+    let path = Path::new(input);
+    let proteomes = if path.is_dir() {
+        collect_directory(input)?
+    } else if path.is_file() && fasta_io::format_extension(path).is_some_and(|ext| ext == "tsv") {
+        read_sample_sheet(input)?
+    } else if path.is_file() {
+        vec![Proteome { species: fasta_io::species_name(path), source: source_of(input, None), metadata: Vec::new(), ss: None }]
+    } else {
+        err::error(err::ERR_GENERAL, Some("Input is not a directory or a file".to_string()));
+    };
+
+    // Species labels must be unique
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for proteome in &proteomes {
+        if let Some(prev) = seen.insert(&proteome.species, proteome.file()) {
+            return Err(format!("Species label {} is given to both {} and {}; please use a sample sheet to assign unique labels", proteome.species, prev, proteome.file()).into());
+        }
+    }
+
+    Ok(proteomes)
+}
+
+// Pair the proteomes with the precomputed 3Di fasta files of the same species name in the directory
+pub fn assign_precomputed_3di(proteomes: &mut [Proteome], dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files: HashMap<String, String> = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && fasta_io::is_fasta(&path) {
+            files.insert(fasta_io::species_name(&path), path.to_string_lossy().into_owned());
+        }
+    }
+    for proteome in proteomes.iter_mut().filter(|p| p.ss.is_none()) {
+        proteome.ss = files.get(&proteome.species).cloned();
+    }
+    Ok(())
+}
+
+fn collect_directory(input: &str) -> Result<Vec<Proteome>, Box<dyn std::error::Error>> {
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(input)? {
+        let path = entry?.path();
+        if path.is_file() { files.push(path); }
+    }
+    files.sort();
+
+    // Gather the annotations first to find the genomes paired with them
+    let mut annotations: HashMap<String, (String, Option<String>)> = HashMap::new();
+    for path in files.iter().filter(|p| is_gff(p)) {
+        let species = fasta_io::species_name(path);
+        if annotations.insert(species.clone(), (path.to_string_lossy().into_owned(), None)).is_some() {
+            err::error(err::ERR_GENERAL, Some(format!("Multiple GFF3 files found for species {}", species)));
+        }
+    }
+
+    let mut proteomes = Vec::new();
+    for path in files.iter().filter(|p| is_flatfile(p)) {
+        proteomes.push(Proteome { species: fasta_io::species_name(path), source: Source::FlatFile(path.to_string_lossy().into_owned()), metadata: Vec::new(), ss: None });
+    }
+    for path in files.iter().filter(|p| fasta_io::is_fasta(p) || is_genome(p)) {
+        let species = fasta_io::species_name(path);
+        let file = path.to_string_lossy().into_owned();
+        if let Some((_, genome)) = annotations.get_mut(&species) {
+            if genome.is_some() {
+                err::error(err::ERR_GENERAL, Some(format!("Multiple genome fasta files found for species {}", species)));
+            }
+            *genome = Some(file);
+        } else if fasta_io::is_fasta(path) {
+            proteomes.push(Proteome { species, source: Source::Fasta(file), metadata: Vec::new(), ss: None });
+        } else {
+            err::warning(err::WRN_GENERAL, Some(format!("Genome {} has no GFF3 annotation; skipping", file)));
+        }
+    }
+    let mut annotations = annotations.into_iter().collect::<Vec<_>>();
+    annotations.sort();
+    for (species, (gff, genome)) in annotations {
+        proteomes.push(Proteome { species, source: Source::Gff { gff, genome }, metadata: Vec::new(), ss: None });
+    }
+
+    Ok(proteomes)
+}
+
+// Rule to take the species label from each fasta header of a merged file
+// A pattern with a capture group is a regex: the 'species' group (or the first group) is the species,
+// and the 'gene' group, if any, is the gene name; otherwise the gene name is the first word of the header
+// Any other pattern is a literal separator between the species and the gene name, e.g. 'species|gene_id'
+pub enum HeaderSpecies {
+    Separator(String),
+    Regex(regex::Regex),
+}
+
+impl HeaderSpecies {
+    pub fn new(pattern: &str) -> Result<HeaderSpecies, String> {
+        if pattern.is_empty() {
+            return Err("Empty pattern for species in fasta header".to_string());
+        }
+        match regex::Regex::new(pattern) {
+            Ok(re) if re.captures_len() > 1 => Ok(HeaderSpecies::Regex(re)),
+            _ => Ok(HeaderSpecies::Separator(pattern.to_string())),
+        }
+    }
+
+    // Split the header into (species, gene name); None if the header does not match
+    pub fn split(&self, header: &str) -> Option<(String, String)> {
+        let (species, gene) = match self {
+            HeaderSpecies::Separator(sep) => {
+                let (species, rest) = header.split_once(sep.as_str())?;
+                (species.trim().to_string(), rest.split_whitespace().next()?.to_string())
+            },
+            HeaderSpecies::Regex(re) => {
+                let caps = re.captures(header)?;
+                let species = caps.name("species").or(caps.get(1))?.as_str().trim().to_string();
+                let gene = match caps.name("gene") {
+                    Some(g) => g.as_str().trim().to_string(),
+                    None => header.split_whitespace().next()?.to_string(),
+                };
+                (species, gene)
+            },
+        };
+        if species.is_empty() || gene.is_empty() { return None; }
+        // Make the label usable in Newick format
+        let species = species.split_whitespace().collect::<Vec<_>>().join("_")
+            .replace(|c: char| "()[]:;,'\"".contains(c), "_");
+        Some((species, gene))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_species() {
+        let separator = HeaderSpecies::new("|").unwrap();
+        assert_eq!(separator.split("Escherichia_coli|b0001 thrL"), Some(("Escherichia_coli".to_string(), "b0001".to_string())));
+        assert_eq!(separator.split("b0001 thrL"), None);
+        let regex = HeaderSpecies::new(r"\[organism=(.+?)\]").unwrap();
+        assert_eq!(regex.split("WP_001 thrL [organism=Escherichia coli K-12]"), Some(("Escherichia_coli_K-12".to_string(), "WP_001".to_string())));
+        let named = HeaderSpecies::new(r"^(?P<gene>\S+)_(?P<species>[A-Z0-9]+)$").unwrap();
+        assert_eq!(named.split("RPOB_ECOLI"), Some(("ECOLI".to_string(), "RPOB".to_string())));
+    }
+}
@@ -0,0 +1,108 @@
+// Translation of nucleotide coding sequences with NCBI genetic code tables
+// Amino acids are listed in the NCBI order of codons (TCAG for each position)
+struct GeneticCode {
+    id: u8,
+    amino_acids: &'static [u8; 64],
+    starts: &'static [&'static str],
+}
+
+const GENETIC_CODES: [GeneticCode; 23] = [
+    GeneticCode { id: 1, amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["TTG", "CTG", "ATG"] },
+    GeneticCode { id: 2, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG", starts: &["ATT", "ATC", "ATA", "ATG", "GTG"] },
+    GeneticCode { id: 3, amino_acids: b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATA", "ATG", "GTG"] },
+    GeneticCode { id: 4, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["TTA", "TTG", "CTG", "ATT", "ATC", "ATA", "ATG", "GTG"] },
+    GeneticCode { id: 5, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG", starts: &["TTG", "ATT", "ATC", "ATA", "ATG", "GTG"] },
+    GeneticCode { id: 6, amino_acids: b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 9, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: &["ATG", "GTG"] },
+    GeneticCode { id: 10, amino_acids: b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 11, amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["TTG", "CTG", "ATT", "ATC", "ATA", "ATG", "GTG"] },
+    GeneticCode { id: 12, amino_acids: b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["CTG", "ATG"] },
+    GeneticCode { id: 13, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG", starts: &["TTG", "ATA", "ATG", "GTG"] },
+    GeneticCode { id: 14, amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 16, amino_acids: b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 21, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: &["ATG", "GTG"] },
+    GeneticCode { id: 22, amino_acids: b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 23, amino_acids: b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATT", "ATG", "GTG"] },
+    GeneticCode { id: 24, amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG", starts: &["TTG", "CTG", "ATG", "GTG"] },
+    GeneticCode { id: 25, amino_acids: b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["TTG", "ATG", "GTG"] },
+    GeneticCode { id: 26, amino_acids: b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["CTG", "ATG"] },
+    GeneticCode { id: 29, amino_acids: b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 30, amino_acids: b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 31, amino_acids: b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: &["ATG"] },
+    GeneticCode { id: 33, amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG", starts: &["TTG", "CTG", "ATG", "GTG"] },
+];
+
+const CODONS: [&str; 64] = [
+    "TTT", "TTC", "TTA", "TTG", "TCT", "TCC", "TCA", "TCG", "TAT", "TAC", "TAA", "TAG", "TGT", "TGC", "TGA", "TGG",
+    "CTT", "CTC", "CTA", "CTG", "CCT", "CCC", "CCA", "CCG", "CAT", "CAC", "CAA", "CAG", "CGT", "CGC", "CGA", "CGG",
+    "ATT", "ATC", "ATA", "ATG", "ACT", "ACC", "ACA", "ACG", "AAT", "AAC", "AAA", "AAG", "AGT", "AGC", "AGA", "AGG",
+    "GTT", "GTC", "GTA", "GTG", "GCT", "GCC", "GCA", "GCG", "GAT", "GAC", "GAA", "GAG", "GGT", "GGC", "GGA", "GGG",
+];
+
+fn genetic_code(id: u8) -> Option<&'static GeneticCode> {
+    GENETIC_CODES.iter().find(|code| code.id == id)
+}
+
+// Check if the given NCBI genetic code table is supported
+pub fn is_valid_code(id: u8) -> bool {
+    genetic_code(id).is_some()
+}
+
+fn base_index(base: u8) -> Option<usize> {
+    match base {
+        b'T' | b't' | b'U' | b'u' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'A' | b'a' => Some(2),
+        b'G' | b'g' => Some(3),
+        _ => None,
+    }
+}
+
+pub fn reverse_complement(seq: &str) -> String {
+    seq.bytes().rev().map(|b| match b {
+        b'A' => 'T', b'T' | b'U' => 'A', b'C' => 'G', b'G' => 'C',
+        b'a' => 't', b't' | b'u' => 'a', b'c' => 'g', b'g' => 'c',
+        b'R' => 'Y', b'Y' => 'R', b'K' => 'M', b'M' => 'K', b'B' => 'V', b'V' => 'B', b'D' => 'H', b'H' => 'D',
+        x => x as char,
+    }).collect()
+}
+
+// Translate a coding sequence with the given genetic code table
+// The first codon is translated to methionine if it is an alternative start codon and the CDS is complete at its 5' end
+// Incomplete trailing codon and terminal stop codon are removed; codons with ambiguous bases become 'X'
+pub fn translate(cds: &str, table: u8, complete_start: bool) -> Option<String> {
+    let code = genetic_code(table)?;
+    let bytes = cds.as_bytes();
+    let mut protein = String::with_capacity(bytes.len() / 3);
+    for (i, codon) in bytes.chunks_exact(3).enumerate() {
+        let aa = match (base_index(codon[0]), base_index(codon[1]), base_index(codon[2])) {
+            (Some(a), Some(b), Some(c)) => {
+                let index = a * 16 + b * 4 + c;
+                if i == 0 && complete_start && code.starts.contains(&CODONS[index]) { 'M' } else { code.amino_acids[index] as char }
+            },
+            _ => 'X',
+        };
+        protein.push(aa);
+    }
+    if protein.ends_with('*') { protein.pop(); }
+    Some(protein)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(translate("ATGGCTTAA", 1, true), Some("MA".to_string()));
+        assert_eq!(translate("GTGAAANNNTGAGG", 11, true), Some("MKX".to_string()));
+        assert_eq!(translate("GTGAAATGA", 1, true), Some("VK".to_string()));
+        assert_eq!(translate("TGGTGA", 2, false), Some("WW".to_string()));
+        assert_eq!(translate("ATG", 7, true), None);
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement("ATGCn"), "nGCAT");
+    }
+}
@@ -0,0 +1,82 @@
+// Validation and cleaning of the input amino acid sequences before database creation
+
+// Residues standing for more than one amino acid
+const AMBIGUOUS: &[char] = &['X', 'B', 'Z', 'J'];
+// Nucleotide alphabet; a sequence made only of these is taken as a nucleotide sequence
+const NUCLEOTIDES: &[char] = &['A', 'C', 'G', 'T', 'U', 'N'];
+// Shorter sequences of the nucleotide alphabet are kept, as they may be low complexity peptides like poly-A or poly-G
+pub const NUCLEOTIDE_MIN_LEN: usize = 30;
+// Reason of dropping a nucleotide sequence
+pub const NUCLEOTIDE: &str = "nucleotide sequence";
+
+// Check if a sequence is made only of nucleotide letters
+pub fn nucleotide_letters(seq: &str) -> bool {
+    !seq.is_empty() && seq.chars().all(|c| NUCLEOTIDES.contains(&c))
+}
+
+pub struct Rules {
+    pub min_len: usize,
+    pub max_len: Option<usize>,
+    // Maximum fraction of ambiguous residues
+    pub max_ambiguous: f64,
+}
+
+// Check a sequence against the rules
+// Returns the cleaned sequence with the changes made, or the reason the sequence is dropped
+pub fn validate(seq: &str, rules: &Rules) -> Result<(String, Vec<String>), String> {
+    let mut changes = Vec::new();
+    let mut seq = seq.to_string();
+    if seq.chars().any(|c| c.is_ascii_lowercase()) {
+        seq = seq.to_ascii_uppercase();
+        changes.push("converted to uppercase".to_string());
+    }
+    let trimmed = seq.trim_end_matches('*');
+    if trimmed.len() < seq.len() {
+        changes.push("removed terminal stop codon".to_string());
+        seq.truncate(trimmed.len());
+    }
+    if let Some(pos) = seq.find('*') {
+        return Err(format!("internal stop codon at position {}", pos + 1));
+    }
+    if let Some(c) = seq.chars().find(|c| !c.is_ascii_alphabetic()) {
+        return Err(format!("invalid character '{}'", c));
+    }
+    if seq.len() >= NUCLEOTIDE_MIN_LEN && nucleotide_letters(&seq) {
+        return Err(NUCLEOTIDE.to_string());
+    }
+    if seq.len() < rules.min_len {
+        return Err(format!("shorter than {} residues", rules.min_len));
+    }
+    if let Some(max_len) = rules.max_len {
+        if seq.len() > max_len {
+            return Err(format!("longer than {} residues", max_len));
+        }
+    }
+    let ambiguous = seq.chars().filter(|c| AMBIGUOUS.contains(c)).count() as f64 / seq.len() as f64;
+    if ambiguous > rules.max_ambiguous {
+        return Err(format!("{:.0}% ambiguous residues", ambiguous * 100.0));
+    }
+    Ok((seq, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let rules = Rules { min_len: 3, max_len: Some(10), max_ambiguous: 0.5 };
+        assert_eq!(validate("MKVL", &rules), Ok(("MKVL".to_string(), vec![])));
+        assert_eq!(validate("mkvL**", &rules), Ok(("MKVL".to_string(), vec!["converted to uppercase".to_string(), "removed terminal stop codon".to_string()])));
+        assert_eq!(validate("MK*VL", &rules), Err("internal stop codon at position 3".to_string()));
+        assert_eq!(validate("MK-VL", &rules), Err("invalid character '-'".to_string()));
+        // Short sequences of nucleotide letters may be peptides
+        assert!(validate("ATGGCCTAA", &rules).is_ok());
+        let long = "ATGGCCAAAGGGTTTCCCAAAGGGTTTCCCTAA";
+        assert_eq!(validate(long, &Rules { max_len: None, ..rules }), Err("nucleotide sequence".to_string()));
+        assert_eq!(validate("MK*", &rules), Err("shorter than 3 residues".to_string()));
+        assert_eq!(validate("MKVLMKVLMKV", &rules), Err("longer than 10 residues".to_string()));
+        assert_eq!(validate("MXXXK", &rules), Err("60% ambiguous residues".to_string()));
+        assert!(validate("MXXKV", &rules).is_ok());
+    }
+}
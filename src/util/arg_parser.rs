@@ -2,6 +2,7 @@ use std::path::PathBuf;
 use clap::{Parser, Subcommand};
 use color_print::cstr;
 use crate::util::arg_parser::Commands::*;
+use crate::seq::afdb_lookup::DownloadPolicy;
 
 #[derive(Parser)]
 #[clap(disable_version_flag = true, arg_required_else_help = true)]
@@ -23,7 +24,48 @@ fn threshold_in_range(s: &str) -> Result<usize, String> {
         Ok(threshold)
     }
 }
-fn _threshold_in_range_f64(s: &str) -> Result<f64, String> {
+// Check if the genetic code table is supported
+fn valid_genetic_code(s: &str) -> Result<u8, String> {
+    let code: u8 = s.parse().map_err(|_| "Not a number".to_string())?;
+    if crate::seq::translate::is_valid_code(code) {
+        Ok(code)
+    } else {
+        Err(format!("Genetic code `{}` is not a supported NCBI translation table", s))
+    }
+}
+// Check if the number is positive
+fn positive_number(s: &str) -> Result<usize, String> {
+    let number: usize = s.parse().map_err(|_| "Not a number".to_string())?;
+    if number == 0 {
+        Err(format!("Number `{}` is not positive", s))
+    } else {
+        Ok(number)
+    }
+}
+// Parse a size in bytes with an optional K, M, G or T suffix
+fn byte_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_uppercase();
+    let upper = upper.trim_end_matches('B');
+    let (number, unit) = match upper.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&upper[..i], c),
+        _ => (upper, ' '),
+    };
+    let shift = match unit { ' ' => 0, 'K' => 10, 'M' => 20, 'G' => 30, 'T' => 40, _ => return Err(format!("Unknown size unit in `{}`", s)) };
+    let number: f64 = number.parse().map_err(|_| format!("Size `{}` is not a number", s))?;
+    if number < 0.0 { return Err(format!("Size `{}` is negative", s)); }
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+// Check if the pLDDT threshold is in range
+fn plddt_in_range(s: &str) -> Result<f32, String> {
+    let threshold: f32 = s.parse().map_err(|_| "Not a number".to_string())?;
+    if !(0.0..=100.0).contains(&threshold) {
+        Err(format!("pLDDT `{}` is not in range 0 to 100", s))
+    } else {
+        Ok(threshold)
+    }
+}
+// Check if the fraction is in range
+fn threshold_in_range_f64(s: &str) -> Result<f64, String> {
     let threshold: f64 = s.parse().map_err(|_| "Not a number".to_string())?;
     if threshold < 0.0 || threshold > 1.0 {
         Err(format!("Threshold `{}` is not in range 0.0 to 1.0", s))
@@ -31,6 +73,19 @@ fn _threshold_in_range_f64(s: &str) -> Result<f64, String> {
         Ok(threshold)
     }
 }
+// Check if the number is not negative
+fn non_negative_f64(s: &str) -> Result<f64, String> {
+    let number: f64 = s.parse().map_err(|_| "Not a number".to_string())?;
+    if number < 0.0 {
+        Err(format!("Number `{}` is negative", s))
+    } else {
+        Ok(number)
+    }
+}
+// Check if the m8 column list has the query and target columns
+fn m8_format(s: &str) -> Result<String, String> {
+    crate::seq::m8::Format::parse(s).map(|_| s.to_string())
+}
 
 // Extra help messages
 const PROFILE_HELP: &str = cstr!(r#"<bold><underline>Example:</underline></bold>
@@ -43,13 +98,19 @@ const GENETREE_HELP: &str = cstr!(r#"<bold><underline>Example:</underline></bold
   # Run gene-tree with the list of hashed gene names; use --realign option to recompute the alignment with custom --threshold option for MSA gap threshold
   <bold>unicore gene-tree --realign --threshold 30 --name /path/to/hashed/gene/names example/tree</bold>
 "#);
+const EASY_SEARCH_HELP: &str = cstr!(r#"<bold><underline>Example:</underline></bold>
+  # Export the core genes of an easy-core run as a reference database
+  <bold>unicore export-core example/results reference/core_db</bold>
+  # Profile new proteomes against the reference core genes and build a tree from them
+  <bold>unicore easy-search new_data reference/core_db new_results weights tmp</bold>
+"#);
 #[derive(Subcommand)]
 #[command(subcommand_help_heading = "Modules")]
 pub enum Commands {
     /// Easy core gene phylogeny workflow, from fasta files to phylogenetic tree
     #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
     EasyCore {
-        /// Input directory with fasta files or a single fasta file
+        /// Input directory with fasta files (optionally gzip, bzip2 or xz compressed), GFF3 files with genome fasta files or GenBank/EMBL files, a sample sheet (.tsv), or a single file
         input: PathBuf,
         /// Output directory where all results will be saved
         output: PathBuf,
@@ -66,16 +127,56 @@ pub enum Commands {
         /// Set maximum sequence length threshold
         #[arg(long)]
         max_len: Option<usize>,
+        /// Drop sequences shorter than this
+        #[arg(long, default_value="2", value_parser = positive_number)]
+        min_len: usize,
+        /// Drop sequences with a larger fraction of ambiguous residues (X, B, Z, J) [0.0 - 1.0]
+        #[arg(long, default_value="0.5", value_parser = threshold_in_range_f64)]
+        max_ambiguous: f64,
         /// Use GPU for foldseek createdb
         #[arg(short, long, default_value="false")]
         gpu: bool,
         /// Use AFDB lookup for foldseek createdb. Useful for large databases
         #[arg(long)]
         afdb_lookup: Option<PathBuf>,
+        /// Base URL to download the AFDB lookup tables from, e.g. a local mirror [default: $UNICORE_AFDB_URL or the official server]
+        #[arg(long)]
+        afdb_url: Option<String>,
+        /// Ignore AFDB lookup hits with a mean pLDDT below this threshold and predict their 3Di sequences instead [0 - 100]
+        #[arg(long, value_parser = plddt_in_range)]
+        min_plddt: Option<f32>,
+        /// What to do if the AFDB lookup tables are missing [ask, always, never, fail] [default: $UNICORE_LOOKUP_DOWNLOAD or ask]
+        #[arg(long, value_parser = DownloadPolicy::parse)]
+        lookup_download: Option<DownloadPolicy>,
+        /// Cache directory of predicted 3Di sequences shared across projects [default: $UNICORE_CACHE]
+        #[arg(long)]
+        cache: Option<PathBuf>,
         /// Use custom lookup database, accepts any Foldseek database to reference against
         #[arg(long)]
         custom_lookup: Option<PathBuf>,
-        /// Arguments for foldseek options in string e.g. -c "-c 0.8"
+        /// NCBI genetic code table for translating CDS of GFF3, GenBank or EMBL inputs; overridden by transl_table of each CDS
+        #[arg(long, default_value="11", value_parser = valid_genetic_code)]
+        genetic_code: u8,
+        /// Take species labels from fasta headers of a merged file, by a separator (e.g. "|" for >species|gene)
+        /// or by a regex with a capture group (e.g. "\[organism=(.+?)\]")
+        #[arg(long, verbatim_doc_comment)]
+        header_species: Option<String>,
+        /// Directory with precomputed 3Di fasta files named after the species; paired by the first word of the headers
+        #[arg(long)]
+        precomputed_3di: Option<PathBuf>,
+        /// Directory with a subdirectory of structure files (PDB, mmCIF, ...) for each species, used instead of ProstT5 prediction
+        #[arg(long)]
+        structures: Option<PathBuf>,
+        /// Number of sequences per ProstT5 prediction shard; an interrupted run resumes from the first unfinished shard
+        #[arg(long, default_value="100000", value_parser = positive_number)]
+        shard_size: usize,
+        /// Clustering backend; foldseek clusters by 3Di and amino acid sequences, mmseqs by amino acid sequences only
+        #[arg(long, default_value="foldseek", value_parser = ["foldseek", "mmseqs"])]
+        backend: String,
+        /// Update the previous clustering with the sequences added to the database by clusterupdate, keeping its clusters where possible
+        #[arg(long, default_value="false")]
+        update: bool,
+        /// Arguments for foldseek or mmseqs options in string e.g. -c "-c 0.8"
         #[arg(short, long, default_value="-c 0.8")]
         cluster_options: String,
         /// Coverage threshold for core structures. [0 - 100]
@@ -112,12 +213,13 @@ pub enum Commands {
         #[arg(short='v', long, default_value="3")]
         verbosity: u8,
     },
-    /// Easy search workflow, from fasta files to phylogenetic tree
-    #[clap(arg_required_else_help = true, allow_hyphen_values = true, hide = true)]
+    /// Easy search workflow, from fasta files to phylogenetic tree by a reference core gene set
+    #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
+    #[command(after_help=EASY_SEARCH_HELP)]
     EasySearch {
-        /// Input directory with fasta files or a single fasta file
+        /// Input directory with fasta files (optionally gzip, bzip2 or xz compressed), GFF3 files with genome fasta files or GenBank/EMBL files, a sample sheet (.tsv), or a single file
         input: PathBuf,
-        /// Target database to search against
+        /// Reference core gene database to search against (export-core output)
         target: PathBuf,
         /// Output directory where all results will be saved
         output: PathBuf,
@@ -134,16 +236,59 @@ pub enum Commands {
         /// Set maximum sequence length threshold
         #[arg(long)]
         max_len: Option<usize>,
+        /// Drop sequences shorter than this
+        #[arg(long, default_value="2", value_parser = positive_number)]
+        min_len: usize,
+        /// Drop sequences with a larger fraction of ambiguous residues (X, B, Z, J) [0.0 - 1.0]
+        #[arg(long, default_value="0.5", value_parser = threshold_in_range_f64)]
+        max_ambiguous: f64,
         /// Use GPU for foldseek createdb
         #[arg(short, long, default_value="false")]
         gpu: bool,
         /// Use AFDB lookup for foldseek createdb. Useful for large databases
         #[arg(long)]
         afdb_lookup: Option<PathBuf>,
+        /// Base URL to download the AFDB lookup tables from, e.g. a local mirror [default: $UNICORE_AFDB_URL or the official server]
+        #[arg(long)]
+        afdb_url: Option<String>,
+        /// Ignore AFDB lookup hits with a mean pLDDT below this threshold and predict their 3Di sequences instead [0 - 100]
+        #[arg(long, value_parser = plddt_in_range)]
+        min_plddt: Option<f32>,
+        /// What to do if the AFDB lookup tables are missing [ask, always, never, fail] [default: $UNICORE_LOOKUP_DOWNLOAD or ask]
+        #[arg(long, value_parser = DownloadPolicy::parse)]
+        lookup_download: Option<DownloadPolicy>,
+        /// Cache directory of predicted 3Di sequences shared across projects [default: $UNICORE_CACHE]
+        #[arg(long)]
+        cache: Option<PathBuf>,
         /// Use custom lookup database, accepts any Foldseek database to reference against
         #[arg(long)]
         custom_lookup: Option<PathBuf>,
-        /// Arguments for foldseek options in string e.g. -s "-c 0.8"
+        /// NCBI genetic code table for translating CDS of GFF3, GenBank or EMBL inputs; overridden by transl_table of each CDS
+        #[arg(long, default_value="11", value_parser = valid_genetic_code)]
+        genetic_code: u8,
+        /// Take species labels from fasta headers of a merged file, by a separator (e.g. "|" for >species|gene)
+        /// or by a regex with a capture group (e.g. "\[organism=(.+?)\]")
+        #[arg(long, verbatim_doc_comment)]
+        header_species: Option<String>,
+        /// Directory with precomputed 3Di fasta files named after the species; paired by the first word of the headers
+        #[arg(long)]
+        precomputed_3di: Option<PathBuf>,
+        /// Directory with a subdirectory of structure files (PDB, mmCIF, ...) for each species, used instead of ProstT5 prediction
+        #[arg(long)]
+        structures: Option<PathBuf>,
+        /// Number of sequences per ProstT5 prediction shard; an interrupted run resumes from the first unfinished shard
+        #[arg(long, default_value="100000", value_parser = positive_number)]
+        shard_size: usize,
+        /// Search backend; foldseek searches by 3Di and amino acid sequences, mmseqs by amino acid sequences only
+        #[arg(long, default_value="foldseek", value_parser = ["foldseek", "mmseqs"])]
+        backend: String,
+        /// Search in both directions and keep only the reciprocal best hits of each target gene in each species
+        #[arg(long, default_value="false")]
+        orthology: bool,
+        /// With --orthology, also keep in-paralogs scoring at least this fraction of the best hit's bit score [0.0 - 1.0]
+        #[arg(long, default_value="1.0", value_parser = threshold_in_range_f64)]
+        inparalog_ratio: f64,
+        /// Arguments for foldseek or mmseqs options in string e.g. -s "-c 0.8"
         #[arg(short, long, default_value="-c 0.8")]
         search_options: String,
         /// Coverage threshold for core structures. [0 - 100]
@@ -152,6 +297,21 @@ pub enum Commands {
         /// Generate tsv with copy number statistics
         #[arg(short, long, default_value="true")]
         print_copiness: bool,
+        /// Columns of the search result, passed to --format-output of foldseek or mmseqs convertalis
+        #[arg(long, value_parser = m8_format)]
+        format_output: Option<String>,
+        /// Ignore hits with a lower bit score
+        #[arg(long)]
+        min_bits: Option<f64>,
+        /// Ignore hits with a higher e-value
+        #[arg(long, value_parser = non_negative_f64)]
+        max_evalue: Option<f64>,
+        /// Ignore hits covering a smaller fraction of the query or target (qcov and tcov columns) [0.0 - 1.0]
+        #[arg(long, value_parser = threshold_in_range_f64)]
+        min_coverage: Option<f64>,
+        /// Ignore hits with a lower TM-score (alntmscore, qtmscore or ttmscore column) [0.0 - 1.0]
+        #[arg(long, value_parser = threshold_in_range_f64)]
+        min_tmscore: Option<f64>,
         /// Multiple sequence aligner [foldmason, mafft-linsi, mafft]
         #[arg(short='A', long, default_value="foldmason")]
         aligner: String,
@@ -183,7 +343,7 @@ pub enum Commands {
     /// Create Foldseek database from amino acid sequences
     #[clap(arg_required_else_help = true, allow_hyphen_values = true, verbatim_doc_comment)]
     Createdb {
-        /// Input directory with fasta files or a single fasta file
+        /// Input directory with fasta files (optionally gzip, bzip2 or xz compressed), GFF3 files with genome fasta files or GenBank/EMBL files, a sample sheet (.tsv), or a single file
         input: PathBuf,
         /// Output foldseek database
         output: PathBuf,
@@ -198,15 +358,52 @@ pub enum Commands {
         /// Set maximum sequence length threshold
         #[arg(long)]
         max_len: Option<usize>,
+        /// Drop sequences shorter than this
+        #[arg(long, default_value="2", value_parser = positive_number)]
+        min_len: usize,
+        /// Drop sequences with a larger fraction of ambiguous residues (X, B, Z, J) [0.0 - 1.0]
+        #[arg(long, default_value="0.5", value_parser = threshold_in_range_f64)]
+        max_ambiguous: f64,
         /// Use GPU for foldseek createdb
         #[arg(short, long, default_value="false")]
         gpu: bool,
         /// Use AFDB lookup for foldseek createdb. Useful for large databases
         #[arg(long)]
         afdb_lookup: Option<PathBuf>,
+        /// Base URL to download the AFDB lookup tables from, e.g. a local mirror [default: $UNICORE_AFDB_URL or the official server]
+        #[arg(long)]
+        afdb_url: Option<String>,
+        /// Ignore AFDB lookup hits with a mean pLDDT below this threshold and predict their 3Di sequences instead [0 - 100]
+        #[arg(long, value_parser = plddt_in_range)]
+        min_plddt: Option<f32>,
+        /// What to do if the AFDB lookup tables are missing [ask, always, never, fail] [default: $UNICORE_LOOKUP_DOWNLOAD or ask]
+        #[arg(long, value_parser = DownloadPolicy::parse)]
+        lookup_download: Option<DownloadPolicy>,
+        /// Cache directory of predicted 3Di sequences shared across projects [default: $UNICORE_CACHE]
+        #[arg(long)]
+        cache: Option<PathBuf>,
         /// Use custom lookup database, accepts any Foldseek database to reference against
         #[arg(long)]
         custom_lookup: Option<PathBuf>,
+        /// NCBI genetic code table for translating CDS of GFF3, GenBank or EMBL inputs; overridden by transl_table of each CDS
+        #[arg(long, default_value="11", value_parser = valid_genetic_code)]
+        genetic_code: u8,
+        /// Take species labels from fasta headers of a merged file, by a separator (e.g. "|" for >species|gene)
+        /// or by a regex with a capture group (e.g. "\[organism=(.+?)\]")
+        #[arg(long, verbatim_doc_comment)]
+        header_species: Option<String>,
+        /// Directory with precomputed 3Di fasta files named after the species; paired by the first word of the headers
+        #[arg(long)]
+        precomputed_3di: Option<PathBuf>,
+        /// Directory with a subdirectory of structure files (PDB, mmCIF, ...) for each species, used instead of ProstT5 prediction
+        #[arg(long)]
+        structures: Option<PathBuf>,
+        /// Number of sequences per ProstT5 prediction shard; an interrupted run resumes from the first unfinished shard
+        #[arg(long, default_value="100000", value_parser = positive_number)]
+        shard_size: usize,
+        /// Add the input species to the existing output database; only the new sequences are predicted
+        #[arg(long, default_value="false")]
+        append: bool,
         /// Number of threads to use; 0 to use all
         #[arg(long, default_value="0")]
         threads: usize,
@@ -234,7 +431,13 @@ pub enum Commands {
         /// Keep intermediate Foldseek cluster database
         #[arg(short, long, default_value="false")]
         keep_cluster_db: bool,
-        /// Arguments for foldseek options in string e.g. -c "-c 0.8"
+        /// Clustering backend; foldseek clusters by 3Di and amino acid sequences, mmseqs by amino acid sequences only
+        #[arg(long, default_value="foldseek", value_parser = ["foldseek", "mmseqs"])]
+        backend: String,
+        /// Update the previous clustering with the sequences added to the database by clusterupdate, keeping its clusters where possible
+        #[arg(long, default_value="false")]
+        update: bool,
+        /// Arguments for foldseek or mmseqs options in string e.g. -c "-c 0.8"
         #[arg(short, long, default_value="-c 0.8")]
         cluster_options: String,
         /// Number of threads to use; 0 to use all
@@ -258,7 +461,19 @@ pub enum Commands {
         /// Keep intermediate Foldseek alignment database
         #[arg(short, long, default_value="false")]
         keep_aln_db: bool,
-        /// Arguments for foldseek options in string e.g. -s "-c 0.8"
+        /// Search backend; foldseek searches by 3Di and amino acid sequences, mmseqs by amino acid sequences only
+        #[arg(long, default_value="foldseek", value_parser = ["foldseek", "mmseqs"])]
+        backend: String,
+        /// Search in both directions and keep only the reciprocal best hits of each target gene in each species
+        #[arg(long, default_value="false")]
+        orthology: bool,
+        /// With --orthology, also keep in-paralogs scoring at least this fraction of the best hit's bit score [0.0 - 1.0]
+        #[arg(long, default_value="1.0", value_parser = threshold_in_range_f64)]
+        inparalog_ratio: f64,
+        /// Columns of the search result, passed to --format-output of foldseek or mmseqs convertalis
+        #[arg(long, value_parser = m8_format)]
+        format_output: Option<String>,
+        /// Arguments for foldseek or mmseqs options in string e.g. -s "-c 0.8"
         #[arg(short, long, default_value="-c 0.8")]
         search_options: String,
         /// Number of threads to use; 0 to use all
@@ -284,6 +499,21 @@ pub enum Commands {
         /// Generate tsv with copy number statistics
         #[arg(short, long, default_value="true")]
         print_copiness: bool,
+        /// Columns of the m8 input as given to --format-output of foldseek or mmseqs convertalis; a header line takes precedence
+        #[arg(long, value_parser = m8_format)]
+        format_output: Option<String>,
+        /// Ignore hits with a lower bit score
+        #[arg(long)]
+        min_bits: Option<f64>,
+        /// Ignore hits with a higher e-value
+        #[arg(long, value_parser = non_negative_f64)]
+        max_evalue: Option<f64>,
+        /// Ignore hits covering a smaller fraction of the query or target (qcov and tcov columns) [0.0 - 1.0]
+        #[arg(long, value_parser = threshold_in_range_f64)]
+        min_coverage: Option<f64>,
+        /// Ignore hits with a lower TM-score (alntmscore, qtmscore or ttmscore column) [0.0 - 1.0]
+        #[arg(long, value_parser = threshold_in_range_f64)]
+        min_tmscore: Option<f64>,
         /// Number of threads to use; 0 to use all
         #[arg(long, default_value="0")]
         threads: usize,
@@ -365,6 +595,83 @@ pub enum Commands {
         #[arg(short='v', long, default_value="3")]
         verbosity: u8,
     },
+    /// Remove species from the createdb output database
+    #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
+    RemoveSpecies {
+        /// Input database (createdb output)
+        db: PathBuf,
+        /// Species to remove, or files listing one species per line
+        #[arg(required = true)]
+        species: Vec<String>,
+        /// Number of threads to use; 0 to use all
+        #[arg(long, default_value="0")]
+        threads: usize,
+        /// Verbosity (0: quiet, 1: +errors, 2: +warnings, 3: +info, 4: +debug)
+        #[arg(short='v', long, default_value="3")]
+        verbosity: u8,
+    },
+    /// Build the binary index of the AFDB lookup tables for faster lookups in createdb
+    #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
+    LookupIndex {
+        /// Directory with the AFDB lookup tables (createdb --afdb-lookup)
+        tables: PathBuf,
+        /// Number of threads to use; 0 to use all
+        #[arg(long, default_value="0")]
+        threads: usize,
+        /// Verbosity (0: quiet, 1: +errors, 2: +warnings, 3: +info, 4: +debug)
+        #[arg(short='v', long, default_value="3")]
+        verbosity: u8,
+    },
+    /// Build AFDB-style lookup tables from local Foldseek databases for offline createdb lookups
+    #[clap(arg_required_else_help = true)]
+    LookupBuild {
+        /// Foldseek databases with amino acid and 3Di (_ss) sequences; merged in the given order
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// Output directory of the lookup tables (createdb --afdb-lookup)
+        output: PathBuf,
+        /// Add the entries to the existing lookup tables
+        #[arg(long, default_value="false")]
+        append: bool,
+        /// Number of threads to use; 0 to use all
+        #[arg(long, default_value="0")]
+        threads: usize,
+        /// Verbosity (0: quiet, 1: +errors, 2: +warnings, 3: +info, 4: +debug)
+        #[arg(short='v', long, default_value="3")]
+        verbosity: u8,
+    },
+    /// Report the size of the 3Di prediction cache, or prune it
+    #[clap(allow_hyphen_values = true)]
+    Cache {
+        /// Cache directory (createdb --cache) [default: $UNICORE_CACHE]
+        dir: Option<PathBuf>,
+        /// Remove the least recently used entries until the cache fits in this size, e.g. 50G
+        #[arg(long, value_parser = byte_size)]
+        max_size: Option<u64>,
+        /// Remove the entries unused for more than this number of days
+        #[arg(long)]
+        max_age: Option<u64>,
+        /// Number of threads to use; 0 to use all
+        #[arg(long, default_value="0")]
+        threads: usize,
+        /// Verbosity (0: quiet, 1: +errors, 2: +warnings, 3: +info, 4: +debug)
+        #[arg(short='v', long, default_value="3")]
+        verbosity: u8,
+    },
+    /// Export the core genes of an easy-core output as a reference database for easy-search
+    #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
+    ExportCore {
+        /// easy-core output directory
+        input: PathBuf,
+        /// Output reference database; the cluster metadata will be saved as OUTPUT.core.tsv
+        output: PathBuf,
+        /// Number of threads to use; 0 to use all
+        #[arg(long, default_value="0")]
+        threads: usize,
+        /// Verbosity (0: quiet, 1: +errors, 2: +warnings, 3: +info, 4: +debug)
+        #[arg(short='v', long, default_value="3")]
+        verbosity: u8,
+    },
     /// Runtime environment configuration
     #[clap(arg_required_else_help = true, allow_hyphen_values = true)]
     Config {
@@ -414,15 +721,32 @@ pub struct Args {
     pub createdb_keep: Option<bool>,
     pub createdb_overwrite: Option<bool>,
     pub createdb_max_len: Option<Option<usize>>,
+    pub createdb_min_len: Option<usize>,
+    pub createdb_max_ambiguous: Option<f64>,
     pub createdb_gpu: Option<bool>,
     pub createdb_afdb_lookup: Option<Option<String>>,
+    pub createdb_afdb_url: Option<Option<String>>,
+    pub createdb_min_plddt: Option<Option<f32>>,
+    pub createdb_lookup_download: Option<Option<DownloadPolicy>>,
+    pub createdb_cache: Option<Option<String>>,
     pub createdb_custom_lookup: Option<Option<String>>,
+    pub createdb_genetic_code: Option<u8>,
+    pub createdb_header_species: Option<Option<String>>,
+    pub createdb_precomputed_3di: Option<Option<String>>,
+    pub createdb_structures: Option<Option<String>>,
+    pub createdb_append: Option<bool>,
+    pub createdb_shard_size: Option<usize>,
 
     pub profile_input_db: Option<String>,
     pub profile_input_tsv: Option<String>,
     pub profile_output: Option<String>,
     pub profile_threshold: Option<usize>,
     pub profile_print_copiness: Option<bool>,
+    pub profile_format_output: Option<Option<String>>,
+    pub profile_min_bits: Option<Option<f64>>,
+    pub profile_max_evalue: Option<Option<f64>>,
+    pub profile_min_coverage: Option<Option<f64>>,
+    pub profile_min_tmscore: Option<Option<f64>>,
 
     pub search_input: Option<String>,
     pub search_target: Option<String>,
@@ -430,12 +754,18 @@ pub struct Args {
     pub search_tmp: Option<String>,
     pub search_keep_aln_db: Option<bool>,
     pub search_search_options: Option<String>,
+    pub search_backend: Option<String>,
+    pub search_orthology: Option<bool>,
+    pub search_inparalog_ratio: Option<f64>,
+    pub search_format_output: Option<Option<String>>,
 
     pub cluster_input: Option<String>,
     pub cluster_output: Option<String>,
     pub cluster_tmp: Option<String>,
     pub cluster_keep_cluster_db: Option<bool>,
     pub cluster_cluster_options: Option<String>,
+    pub cluster_backend: Option<String>,
+    pub cluster_update: Option<bool>,
 
     pub tree_db: Option<String>,
     pub tree_input: Option<String>,
@@ -456,6 +786,22 @@ pub struct Args {
     pub genetree_aligner: Option<String>,
     pub genetree_aligner_options: Option<Option<String>>,
 
+    pub removespecies_db: Option<String>,
+    pub removespecies_species: Option<Vec<String>>,
+
+    pub lookupindex_tables: Option<String>,
+
+    pub lookupbuild_sources: Option<Vec<String>>,
+    pub lookupbuild_output: Option<String>,
+    pub lookupbuild_append: Option<bool>,
+
+    pub cache_dir: Option<Option<String>>,
+    pub cache_max_size: Option<Option<u64>>,
+    pub cache_max_age: Option<Option<u64>>,
+
+    pub exportcore_input: Option<String>,
+    pub exportcore_output: Option<String>,
+
     pub config_check: Option<bool>,
     pub config_set_mmseqs: Option<String>,
     pub config_set_foldseek: Option<String>,
@@ -477,6 +823,11 @@ impl Args {
             Some(Cluster { verbosity, .. }) => *verbosity,
             Some(Tree { verbosity, .. }) => *verbosity,
             Some(GeneTree { verbosity, .. }) => *verbosity,
+            Some(RemoveSpecies { verbosity, .. }) => *verbosity,
+            Some(LookupIndex { verbosity, .. }) => *verbosity,
+            Some(LookupBuild { verbosity, .. }) => *verbosity,
+            Some(Cache { verbosity, .. }) => *verbosity,
+            Some(ExportCore { verbosity, .. }) => *verbosity,
             Some(EasyCore { verbosity, .. }) => *verbosity,
             Some(EasySearch { verbosity, .. }) => *verbosity,
             Some(Config { verbosity, .. }) => *verbosity,
@@ -489,6 +840,11 @@ impl Args {
             Some(Cluster { threads, .. }) => *threads,
             Some(Tree { threads, .. }) => *threads,
             Some(GeneTree { threads, .. }) => *threads,
+            Some(RemoveSpecies { threads, .. }) => *threads,
+            Some(LookupIndex { threads, .. }) => *threads,
+            Some(LookupBuild { threads, .. }) => *threads,
+            Some(Cache { threads, .. }) => *threads,
+            Some(ExportCore { threads, .. }) => *threads,
             Some(EasyCore { threads, .. }) => *threads,
             Some(EasySearch { threads, .. }) => *threads,
             _ => 0,
@@ -524,6 +880,16 @@ impl Args {
             Some(EasyCore { max_len, .. }) => Some(max_len.clone()),
             Some(EasySearch { max_len, .. }) => Some(max_len.clone()), _ => None,
         };
+        let createdb_min_len = match &args.command {
+            Some(Createdb { min_len, .. }) => Some(*min_len),
+            Some(EasyCore { min_len, .. }) => Some(*min_len),
+            Some(EasySearch { min_len, .. }) => Some(*min_len), _ => None,
+        };
+        let createdb_max_ambiguous = match &args.command {
+            Some(Createdb { max_ambiguous, .. }) => Some(*max_ambiguous),
+            Some(EasyCore { max_ambiguous, .. }) => Some(*max_ambiguous),
+            Some(EasySearch { max_ambiguous, .. }) => Some(*max_ambiguous), _ => None,
+        };
         let createdb_gpu = match &args.command {
             Some(Createdb { gpu, .. }) => Some(*gpu),
             Some(EasyCore { gpu, .. }) => Some(*gpu),
@@ -534,11 +900,61 @@ impl Args {
             Some(EasyCore { afdb_lookup, .. }) => match afdb_lookup { Some(p) => Some(Some(own(p))), _none => Some(None) },
             Some(EasySearch { afdb_lookup, .. }) => match afdb_lookup { Some(p) => Some(Some(own(p))), _none => Some(None) }, _ => None,
         };
+        let createdb_afdb_url = match &args.command {
+            Some(Createdb { afdb_url, .. }) => Some(afdb_url.clone()),
+            Some(EasyCore { afdb_url, .. }) => Some(afdb_url.clone()),
+            Some(EasySearch { afdb_url, .. }) => Some(afdb_url.clone()), _ => None,
+        };
+        let createdb_min_plddt = match &args.command {
+            Some(Createdb { min_plddt, .. }) => Some(*min_plddt),
+            Some(EasyCore { min_plddt, .. }) => Some(*min_plddt),
+            Some(EasySearch { min_plddt, .. }) => Some(*min_plddt), _ => None,
+        };
+        let createdb_lookup_download = match &args.command {
+            Some(Createdb { lookup_download, .. }) => Some(*lookup_download),
+            Some(EasyCore { lookup_download, .. }) => Some(*lookup_download),
+            Some(EasySearch { lookup_download, .. }) => Some(*lookup_download), _ => None,
+        };
+        let createdb_cache = match &args.command {
+            Some(Createdb { cache, .. }) => Some(cache.as_ref().map(own)),
+            Some(EasyCore { cache, .. }) => Some(cache.as_ref().map(own)),
+            Some(EasySearch { cache, .. }) => Some(cache.as_ref().map(own)), _ => None,
+        };
         let createdb_custom_lookup = match &args.command {
             Some(Createdb { custom_lookup, .. }) => match custom_lookup { Some(p) => Some(Some(own(p))), _none => Some(None) },
             Some(EasyCore { custom_lookup, .. }) => match custom_lookup { Some(p) => Some(Some(own(p))), _none => Some(None) },
             Some(EasySearch { custom_lookup, .. }) => match custom_lookup { Some(p) => Some(Some(own(p))), _none => Some(None) }, _ => None,
         };
+        let createdb_genetic_code = match &args.command {
+            Some(Createdb { genetic_code, .. }) => Some(*genetic_code),
+            Some(EasyCore { genetic_code, .. }) => Some(*genetic_code),
+            Some(EasySearch { genetic_code, .. }) => Some(*genetic_code), _ => None,
+        };
+        let createdb_header_species = match &args.command {
+            Some(Createdb { header_species, .. }) => Some(header_species.clone()),
+            Some(EasyCore { header_species, .. }) => Some(header_species.clone()),
+            Some(EasySearch { header_species, .. }) => Some(header_species.clone()), _ => None,
+        };
+        let createdb_precomputed_3di = match &args.command {
+            Some(Createdb { precomputed_3di, .. }) => match precomputed_3di { Some(p) => Some(Some(own(p))), _none => Some(None) },
+            Some(EasyCore { precomputed_3di, .. }) => match precomputed_3di { Some(p) => Some(Some(own(p))), _none => Some(None) },
+            Some(EasySearch { precomputed_3di, .. }) => match precomputed_3di { Some(p) => Some(Some(own(p))), _none => Some(None) }, _ => None,
+        };
+        let createdb_structures = match &args.command {
+            Some(Createdb { structures, .. }) => match structures { Some(p) => Some(Some(own(p))), _none => Some(None) },
+            Some(EasyCore { structures, .. }) => match structures { Some(p) => Some(Some(own(p))), _none => Some(None) },
+            Some(EasySearch { structures, .. }) => match structures { Some(p) => Some(Some(own(p))), _none => Some(None) }, _ => None,
+        };
+        let createdb_append = match &args.command {
+            Some(Createdb { append, .. }) => Some(*append),
+            Some(EasyCore { .. }) => Some(false),
+            Some(EasySearch { .. }) => Some(false), _ => None,
+        };
+        let createdb_shard_size = match &args.command {
+            Some(Createdb { shard_size, .. }) => Some(*shard_size),
+            Some(EasyCore { shard_size, .. }) => Some(*shard_size),
+            Some(EasySearch { shard_size, .. }) => Some(*shard_size), _ => None,
+        };
 
         let profile_input_db = match &args.command {
             Some(Profile { input_db, .. }) => Some(own(input_db)),
@@ -565,6 +981,31 @@ impl Args {
             Some(EasyCore { print_copiness, .. }) => Some(*print_copiness),
             Some(EasySearch { print_copiness, .. }) => Some(*print_copiness), _ => None,
         };
+        let profile_format_output = match &args.command {
+            Some(Profile { format_output, .. }) => Some(format_output.clone()),
+            Some(EasyCore { .. }) => Some(None),
+            Some(EasySearch { format_output, .. }) => Some(format_output.clone()), _ => None,
+        };
+        let profile_min_bits = match &args.command {
+            Some(Profile { min_bits, .. }) => Some(*min_bits),
+            Some(EasyCore { .. }) => Some(None),
+            Some(EasySearch { min_bits, .. }) => Some(*min_bits), _ => None,
+        };
+        let profile_max_evalue = match &args.command {
+            Some(Profile { max_evalue, .. }) => Some(*max_evalue),
+            Some(EasyCore { .. }) => Some(None),
+            Some(EasySearch { max_evalue, .. }) => Some(*max_evalue), _ => None,
+        };
+        let profile_min_coverage = match &args.command {
+            Some(Profile { min_coverage, .. }) => Some(*min_coverage),
+            Some(EasyCore { .. }) => Some(None),
+            Some(EasySearch { min_coverage, .. }) => Some(*min_coverage), _ => None,
+        };
+        let profile_min_tmscore = match &args.command {
+            Some(Profile { min_tmscore, .. }) => Some(*min_tmscore),
+            Some(EasyCore { .. }) => Some(None),
+            Some(EasySearch { min_tmscore, .. }) => Some(*min_tmscore), _ => None,
+        };
 
         let search_input = match &args.command {
             Some(Search { input, .. }) => Some(own(input)),
@@ -590,6 +1031,22 @@ impl Args {
             Some(Search { search_options, .. }) => Some(search_options.clone()),
             Some(EasySearch { search_options, .. }) => Some(search_options.clone()), _ => None,
         };
+        let search_backend = match &args.command {
+            Some(Search { backend, .. }) => Some(backend.clone()),
+            Some(EasySearch { backend, .. }) => Some(backend.clone()), _ => None,
+        };
+        let search_orthology = match &args.command {
+            Some(Search { orthology, .. }) => Some(*orthology),
+            Some(EasySearch { orthology, .. }) => Some(*orthology), _ => None,
+        };
+        let search_inparalog_ratio = match &args.command {
+            Some(Search { inparalog_ratio, .. }) => Some(*inparalog_ratio),
+            Some(EasySearch { inparalog_ratio, .. }) => Some(*inparalog_ratio), _ => None,
+        };
+        let search_format_output = match &args.command {
+            Some(Search { format_output, .. }) => Some(format_output.clone()),
+            Some(EasySearch { format_output, .. }) => Some(format_output.clone()), _ => None,
+        };
 
         let cluster_input = match &args.command {
             Some(Cluster { input, .. }) => Some(own(input)),
@@ -611,6 +1068,14 @@ impl Args {
             Some(Cluster { cluster_options, .. }) => Some(cluster_options.clone()),
             Some(EasyCore { cluster_options, .. }) => Some(cluster_options.clone()), _ => None,
         };
+        let cluster_backend = match &args.command {
+            Some(Cluster { backend, .. }) => Some(backend.clone()),
+            Some(EasyCore { backend, .. }) => Some(backend.clone()), _ => None,
+        };
+        let cluster_update = match &args.command {
+            Some(Cluster { update, .. }) => Some(*update),
+            Some(EasyCore { update, .. }) => Some(*update), _ => None,
+        };
 
         let tree_db = match &args.command {
             Some(Tree { db, .. }) => Some(own(db)),
@@ -683,6 +1148,44 @@ impl Args {
             Some(GeneTree { threshold, .. }) => Some(*threshold), _ => None,
         };
 
+        let removespecies_db = match &args.command {
+            Some(RemoveSpecies { db, .. }) => Some(own(db)), _ => None,
+        };
+        let removespecies_species = match &args.command {
+            Some(RemoveSpecies { species, .. }) => Some(species.clone()), _ => None,
+        };
+
+        let lookupindex_tables = match &args.command {
+            Some(LookupIndex { tables, .. }) => Some(own(tables)), _ => None,
+        };
+
+        let lookupbuild_sources = match &args.command {
+            Some(LookupBuild { sources, .. }) => Some(sources.iter().map(own).collect()), _ => None,
+        };
+        let lookupbuild_output = match &args.command {
+            Some(LookupBuild { output, .. }) => Some(own(output)), _ => None,
+        };
+        let lookupbuild_append = match &args.command {
+            Some(LookupBuild { append, .. }) => Some(*append), _ => None,
+        };
+
+        let cache_dir = match &args.command {
+            Some(Cache { dir, .. }) => Some(dir.as_ref().map(own)), _ => None,
+        };
+        let cache_max_size = match &args.command {
+            Some(Cache { max_size, .. }) => Some(*max_size), _ => None,
+        };
+        let cache_max_age = match &args.command {
+            Some(Cache { max_age, .. }) => Some(*max_age), _ => None,
+        };
+
+        let exportcore_input = match &args.command {
+            Some(ExportCore { input, .. }) => Some(own(input)), _ => None,
+        };
+        let exportcore_output = match &args.command {
+            Some(ExportCore { output, .. }) => Some(own(output)), _ => None,
+        };
+
         let config_check = match &args.command {
             Some(Config { check, .. }) => Some(*check), _ => None,
         };
@@ -713,12 +1216,17 @@ impl Args {
 
         Args {
             command: args.command, version: args.version, threads, verbosity,
-            createdb_input, createdb_output, createdb_model, createdb_keep, createdb_overwrite, createdb_max_len, createdb_gpu, createdb_afdb_lookup, createdb_custom_lookup,
-            profile_input_db, profile_input_tsv, profile_output, profile_threshold, profile_print_copiness,
-            search_input, search_target, search_output, search_tmp, search_keep_aln_db, search_search_options,
-            cluster_input, cluster_output, cluster_tmp, cluster_keep_cluster_db, cluster_cluster_options,
+            createdb_input, createdb_output, createdb_model, createdb_keep, createdb_overwrite, createdb_max_len, createdb_min_len, createdb_max_ambiguous, createdb_gpu, createdb_afdb_lookup, createdb_afdb_url, createdb_min_plddt, createdb_lookup_download, createdb_cache, createdb_custom_lookup, createdb_genetic_code, createdb_header_species, createdb_precomputed_3di, createdb_structures, createdb_append, createdb_shard_size,
+            profile_input_db, profile_input_tsv, profile_output, profile_threshold, profile_print_copiness, profile_format_output, profile_min_bits, profile_max_evalue, profile_min_coverage, profile_min_tmscore,
+            search_input, search_target, search_output, search_tmp, search_keep_aln_db, search_search_options, search_backend, search_orthology, search_inparalog_ratio, search_format_output,
+            cluster_input, cluster_output, cluster_tmp, cluster_keep_cluster_db, cluster_cluster_options, cluster_backend, cluster_update,
             tree_db, tree_input, tree_output, tree_aligner, tree_no_inference, tree_tree_builder, tree_aligner_options, tree_tree_options, tree_threshold,
             genetree_input, genetree_names, genetree_tree_builder, genetree_tree_options, genetree_realign, genetree_aligner, genetree_aligner_options, genetree_threshold,
+            removespecies_db, removespecies_species,
+            lookupindex_tables,
+            lookupbuild_sources, lookupbuild_output, lookupbuild_append,
+            cache_dir, cache_max_size, cache_max_age,
+            exportcore_input, exportcore_output,
             config_check, config_set_mmseqs, config_set_foldseek, config_set_foldmason, config_set_mafft, config_set_mafft_linsi, config_set_iqtree, config_set_fasttree, config_set_raxml,
         }
     }
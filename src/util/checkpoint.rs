@@ -7,4 +7,21 @@ pub fn write_checkpoint(checkpoint_file: &str, content: &str) -> Result<(), Box<
 pub fn read_checkpoint(checkpoint_file: &str) -> Result<String, Box<dyn std::error::Error>> {
     let content = std::fs::read_to_string(checkpoint_file)?;
     Ok(content)
-}
\ No newline at end of file
+}
+
+// Fingerprint of a database by its index, to tell if the database changed since a module was run
+pub fn db_fingerprint(db: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(format!("{:x}", md5::compute(std::fs::read(format!("{}.index", db))?)))
+}
+
+// Record the fingerprint of the database a module was run on, next to its results
+pub fn write_fingerprint(fingerprint_file: &str, db: &str) -> Result<(), Box<dyn std::error::Error>> {
+    write_checkpoint(fingerprint_file, &db_fingerprint(db)?)
+}
+
+// Check if the database changed since the results with the fingerprint were made
+// Results without a fingerprint, e.g. from an older version, are taken as up to date
+pub fn is_stale(fingerprint_file: &str, db: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !std::path::Path::new(fingerprint_file).exists() { return Ok(false); }
+    Ok(read_checkpoint(fingerprint_file)? != db_fingerprint(db)?)
+}
@@ -62,4 +62,8 @@ pub fn _run_at(cmd: &mut std::process::Command, path: &std::path::Path) {
     } else {
         err::error(err::ERR_GENERAL, Some(format!("Could not run command {}", cmdstr)));
     }
-}
\ No newline at end of file
+}
+// Name of an MMseqs2 module for the backend; Foldseek exposes the modules it inherits with the base: prefix
+pub fn base_module(backend: &str, module: &str) -> String {
+    if backend == "foldseek" { format!("base:{}", module) } else { module.to_string() }
+}
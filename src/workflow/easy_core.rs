@@ -1,6 +1,7 @@
 use crate::util::arg_parser::Args;
 use crate::util::message::println_message as mprintln;
 use crate::util::checkpoint::read_checkpoint as read_chkpnt;
+use crate::util::checkpoint::is_stale;
 use crate::envs::variables as var;
 use crate::envs::error_handler as err;
 
@@ -46,10 +47,16 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
     } else {
         err::error(err::ERR_GENERAL, Some("Could not obtain parent directory of the output".to_string()))
     };
+    // The clustering is stale if the database changed since it was made
+    let input = args.cluster_input.clone().unwrap_or_else(|| { err::error(err::ERR_ARGPARSE, Some("cluster - input".to_string())); });
+    let changed = is_stale(&format!("{}.fingerprint", output), &input)?;
     // Check if the checkpoint file exists
     if std::path::Path::new(&format!("{}/cluster.chk", parent)).exists() {
         let content = read_chkpnt(&format!("{}/cluster.chk", parent))?;
-        if content == "1" {
+        if content == "1" && changed {
+            mprintln(&"Database changed since the last clustering, running cluster module".to_string(), 3);
+            cluster(args, bin)?;
+        } else if content == "1" {
             mprintln(&"Clustered database already exists, skipping cluster module".to_string(), 3);
         } else {
             mprintln(&"Running cluster module".to_string(), 3);
@@ -63,9 +70,13 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
     // Run the profile module
     // Check if {output} directory has a checkpoint file
     let output = args.profile_output.clone().unwrap_or_else(|| { crate::envs::error_handler::error(crate::envs::error_handler::ERR_ARGPARSE, Some("profile - output".to_string())); });
+    let changed = is_stale(&format!("{}/profile.fingerprint", output), &input)?;
     if std::path::Path::new(&format!("{}/profile.chk", output)).exists() {
         let content = read_chkpnt(&format!("{}/profile.chk", output))?;
-        if content == "1" {
+        if content == "1" && changed {
+            mprintln(&"Database changed since the last profiling, running profile module".to_string(), 3);
+            profile(args, bin)?;
+        } else if content == "1" {
             mprintln(&"Profiled database already exists, skipping profile module".to_string(), 3);
         } else {
             mprintln(&"Running profile module".to_string(), 3);
@@ -79,9 +90,13 @@ pub fn run(args: &Args, bin: &var::BinaryPaths) -> Result<(), Box<dyn std::error
     // Run the tree module
     // Check if {output} directory has a checkpoint file
     let output = args.tree_output.clone().unwrap_or_else(|| { crate::envs::error_handler::error(crate::envs::error_handler::ERR_ARGPARSE, Some("tree - output".to_string())); });
+    let changed = is_stale(&format!("{}/tree.fingerprint", output), &input)?;
     if std::path::Path::new(&format!("{}/tree.chk", output)).exists() {
         let content = read_chkpnt(&format!("{}/tree.chk", output))?;
-        if content == "1" {
+        if content == "1" && changed {
+            mprintln(&"Database changed since the last tree inference, running tree module".to_string(), 3);
+            tree(args, bin)?;
+        } else if content == "1" {
             mprintln(&"Tree output directory not empty, skipping tree module".to_string(), 3);
         } else {
             mprintln(&"Running tree module".to_string(), 3);